@@ -0,0 +1,99 @@
+//! Picking from large libraries. A pick loads and saves the library once, so
+//! its time follows the library size, whatever the number of picked articles.
+//!
+//! Run with `cargo bench --bench pick`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use pickpocket::{
+    Configuration, Filter, Library, LibraryStore, Opening, YamlStore, SCHEMA_VERSION,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+static SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+static QUANTITIES: [usize; 2] = [1, 20];
+
+fn library(size: usize) -> Library {
+    let articles: Map<String, Value> = (0..size)
+        .map(|id| {
+            let article = json!({
+                "id": id.to_string(),
+                "url": format!("https://site{}.example.com/articles/{}", id % 100, id),
+                "title": format!("Article number {}", id),
+                "excerpt": "A few sentences describing what the article is about.",
+                "word_count": 300 + id % 5000,
+                "time_added": 1_500_000_000 + id as i64,
+                "tags": if id % 3 == 0 { vec!["rust"] } else { vec![] },
+            });
+            (id.to_string(), article)
+        })
+        .collect();
+
+    serde_json::from_value(json!({
+        "schema_version": SCHEMA_VERSION,
+        "read": { "articles": {} },
+        "unread": { "articles": articles },
+    }))
+    .unwrap()
+}
+
+fn configuration(home: &Path) -> Configuration {
+    Configuration {
+        home_folder: home.to_path_buf(),
+        library_file: home.join("library_file"),
+        history_file: home.join("history"),
+        outbox_file: home.join("outbox"),
+        ..Default::default()
+    }
+}
+
+fn pick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pick");
+    group.sample_size(10);
+
+    for &size in &SIZES {
+        let home = tempfile::tempdir().unwrap();
+        let config = configuration(home.path());
+        let store = YamlStore {
+            path: config.library_file.clone(),
+            cipher: Default::default(),
+        };
+        let library = library(size);
+        let mut rng = StdRng::seed_from_u64(size as u64);
+
+        group.throughput(Throughput::Elements(size as u64));
+        for &quantity in &QUANTITIES {
+            group.bench_with_input(
+                BenchmarkId::new(format!("quantity {}", quantity), size),
+                &quantity,
+                |b, &quantity| {
+                    b.iter_batched(
+                        || {
+                            store.save(&library).unwrap();
+                            std::fs::remove_file(&config.outbox_file).ok();
+                            std::fs::remove_file(&config.history_file).ok();
+                        },
+                        |_| {
+                            Library::pick_with_rng(
+                                &config,
+                                Some(quantity),
+                                &Filter::new(),
+                                Opening::Library,
+                                &mut rng,
+                            )
+                            .unwrap()
+                        },
+                        BatchSize::PerIteration,
+                    )
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, pick);
+criterion_main!(benches);
@@ -1,19 +1,32 @@
 use crate::articles::article::Article;
+use crate::articles::response::{RetrieveResponse, SendResponse};
 use crate::authentication::token_handler::TokenHandler;
 use crate::configuration::Configuration;
-use crate::logger;
+use crate::error::{PickpocketError, Result};
 use futures::future::join_all;
 use serde_json::{json, Value};
 
 static ACTION_ARCHIVE: &str = "archive";
+static STATE_ALL: &str = "all";
 static STATE_UNREAD: &str = "unread";
-static PAGE_SIZE: usize = 30;
-static MAX_CONCURRENT_REQUESTS: usize = 5;
 
+/// Most items Pocket sends per page, whatever `count` asks for. Larger
+/// `page_size` settings are refused: a page cut short by Pocket would be
+/// taken for the last one.
+pub static MAX_PAGE_SIZE: usize = 30;
+
+/// Client for Pocket's v3 retrieve and modify endpoints.
+#[allow(clippy::upper_case_acronyms)]
 pub struct API {
     configuration: Configuration,
 }
 
+impl Default for API {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl API {
     pub fn new() -> Self {
         Self {
@@ -21,209 +34,156 @@ impl API {
         }
     }
 
-    pub fn retrieve(&self) -> serde_json::Value {
+    pub fn with_configuration(configuration: Configuration) -> Self {
+        Self { configuration }
+    }
+
+    /// Retrieves every unread article from Pocket, merging all pages into a
+    /// single response.
+    pub fn retrieve(&self) -> Result<RetrieveResponse> {
+        self.retrieve_since(None)
+    }
+
+    /// Retrieves what changed on Pocket since the `since` timestamp of a
+    /// previous response: new and updated items, including archived and deleted
+    /// ones. Without `since`, retrieves every unread article.
+    pub fn retrieve_since(&self, since: Option<i64>) -> Result<RetrieveResponse> {
         // Create a runtime for async operations
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(self.retrieve_async())
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(self.retrieve_since_async(since))
     }
 
-    async fn retrieve_async(&self) -> serde_json::Value {
-        let token_handler = TokenHandler::new();
-        let (consumer_key, pocket_retrieve_url, access_token) = (
-            &self.configuration.consumer_key,
-            &self.configuration.pocket_retrieve_url,
-            &token_handler.read_auth(),
-        );
+    /// Async version of [`API::retrieve`].
+    pub async fn retrieve_async(&self) -> Result<RetrieveResponse> {
+        self.retrieve_since_async(None).await
+    }
 
+    /// Async version of [`API::retrieve_since`], fetching pages in parallel batches.
+    pub async fn retrieve_since_async(&self, since: Option<i64>) -> Result<RetrieveResponse> {
+        let token_handler = TokenHandler::with_configuration(self.configuration.clone());
+        let page_size = self.configuration.page_size;
+        if page_size > MAX_PAGE_SIZE {
+            return Err(Configuration::page_size_too_large(page_size));
+        }
+        let access_token = token_handler.read_auth()?;
         let client = reqwest::Client::new();
 
         // Get the first page to determine how many items we have
-        logger::log("Retrieving first page of articles...");
-        let first_page = self
-            .fetch_page(&client, pocket_retrieve_url, consumer_key, access_token, 0)
-            .await;
-
-        if first_page.is_null() {
-            logger::log("Could not retrieve Pocket's data");
-            return serde_json::Value::Null;
-        }
-
-        // Extract the list of articles from the first page
-        let mut all_items = match first_page["list"].as_object() {
-            Some(items) => items.clone(),
-            None => {
-                logger::log("No items found in the first page");
-                return first_page;
-            }
-        };
-
-        // Check if we need to fetch more pages
-        let first_page_count = all_items.len();
-        logger::log(&format!(
-            "Retrieved {} articles from first page",
-            first_page_count
-        ));
+        let mut response = self
+            .fetch_page(&client, &access_token, since, 0, page_size)
+            .await?;
 
-        if first_page_count == 0 || first_page_count < PAGE_SIZE {
+        if response.list.len() < page_size {
             // No more pages to fetch
-            return first_page;
+            return Ok(response);
         }
 
         // Fetch additional pages in parallel
-        logger::log("Fetching additional pages in parallel...");
-        let mut offset = PAGE_SIZE;
+        let mut offset = page_size;
 
         // Create futures for each page request, processing in batches for controlled concurrency
         loop {
             let mut batch_futures = Vec::new();
 
-            for _ in 0..MAX_CONCURRENT_REQUESTS {
+            for _ in 0..self.configuration.max_concurrent_requests {
                 batch_futures.push(self.fetch_page(
                     &client,
-                    pocket_retrieve_url,
-                    consumer_key,
-                    access_token,
+                    &access_token,
+                    since,
                     offset,
+                    page_size,
                 ));
-                offset += PAGE_SIZE;
-
-                // Safety limit to prevent too many requests
-                if offset > PAGE_SIZE * 50 {
-                    break;
-                }
+                offset += page_size;
             }
 
             // Process this batch of requests
             let batch_results = join_all(batch_futures).await;
-            let mut empty_page_found = false;
+            let mut last_page_found = false;
+            let mut new_items = false;
 
             // Process the results from this batch
             for page_result in batch_results {
-                if let Some(items) = page_result["list"].as_object() {
-                    if items.is_empty() {
-                        empty_page_found = true;
-                        break;
-                    }
-
-                    logger::log(&format!("Retrieved {} articles from page", items.len()));
-
-                    // Add items to our collection
-                    for (id, item) in items {
-                        all_items.insert(id.clone(), item.clone());
-                    }
-                } else {
-                    empty_page_found = true;
-                    break;
+                let page = page_result?;
+
+                // A page that is not full is the last one
+                if page.list.len() < page_size {
+                    last_page_found = true;
                 }
-            }
 
-            if empty_page_found {
-                break;
+                // Add items to our collection
+                for (id, item) in page.list {
+                    new_items |= response.list.insert(id, item).is_none();
+                }
             }
 
-            // If we've hit our safety limit, stop
-            if offset > PAGE_SIZE * 50 {
-                logger::log("Reached maximum number of pages, stopping pagination");
+            // A server ignoring `offset` would send the same full pages forever
+            if last_page_found || !new_items {
                 break;
             }
         }
 
-        logger::log(&format!("Total articles retrieved: {}", all_items.len()));
-
-        // Construct the final response
-        json!({
-            "status": 1,
-            "list": all_items
-        })
+        Ok(response)
     }
 
     async fn fetch_page(
         &self,
         client: &reqwest::Client,
-        url: &str,
-        consumer_key: &str,
         access_token: &str,
+        since: Option<i64>,
         offset: usize,
-    ) -> Value {
-        let page_num = (offset / PAGE_SIZE) + 1;
-        logger::log(&format!(
-            "Retrieving page {} (offset: {})",
-            page_num, offset
-        ));
-
-        let params = [
-            ("consumer_key", consumer_key),
-            ("access_token", access_token),
-            ("state", &STATE_UNREAD.to_owned()),
-            ("count", &PAGE_SIZE.to_string()),
-            ("offset", &offset.to_string()),
-            ("detailType", &"simple".to_owned()),
+        page_size: usize,
+    ) -> Result<RetrieveResponse> {
+        let mut params = vec![
+            ("consumer_key", self.configuration.consumer_key.to_owned()),
+            ("access_token", access_token.to_owned()),
+            ("count", page_size.to_string()),
+            ("offset", offset.to_string()),
+            ("detailType", "complete".to_owned()),
         ];
 
-        match client.post(url).form(&params).send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    match response.text().await {
-                        Ok(text) => match serde_json::from_str(&text) {
-                            Ok(json) => {
-                                let json: Value = json;
-                                if let Some(items) = json["list"].as_object() {
-                                    logger::log(&format!(
-                                        "Page {} contains {} items",
-                                        page_num,
-                                        items.len()
-                                    ));
-                                }
-                                json
-                            }
-                            Err(e) => {
-                                logger::log(&format!(
-                                    "Error parsing JSON from page {}: {}",
-                                    page_num, e
-                                ));
-                                Value::Null
-                            }
-                        },
-                        Err(e) => {
-                            logger::log(&format!(
-                                "Error reading response text from page {}: {}",
-                                page_num, e
-                            ));
-                            Value::Null
-                        }
-                    }
-                } else {
-                    logger::log(&format!(
-                        "Error response from page {}: {}",
-                        page_num,
-                        response.status()
-                    ));
-                    Value::Null
-                }
-            }
-            Err(e) => {
-                logger::log(&format!("Error fetching page {}: {}", page_num, e));
-                Value::Null
+        // Incremental syncs need archived and deleted items too
+        match since {
+            Some(since) => {
+                params.push(("state", STATE_ALL.to_owned()));
+                params.push(("since", since.to_string()));
             }
+            None => params.push(("state", STATE_UNREAD.to_owned())),
+        }
+
+        let response = client
+            .post(&self.configuration.pocket_retrieve_url)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(PickpocketError::from_response(&response));
+        }
+
+        let text = response.text().await?;
+        let page: RetrieveResponse = serde_json::from_str(&text)?;
+
+        match page.error {
+            Some(message) => Err(PickpocketError::Pocket {
+                status: 200,
+                message,
+            }),
+            None => Ok(page),
         }
     }
 
-    pub fn archive(&self, articles: Vec<&Article>) {
+    /// Archives the given articles on Pocket, returning the ids of the articles
+    /// Pocket confirmed archiving.
+    pub fn archive(&self, articles: Vec<&Article>) -> Result<Vec<String>> {
         // Create a runtime for async operations
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(self.archive_async(articles));
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(self.archive_async(articles))
     }
 
-    async fn archive_async(&self, articles: Vec<&Article>) {
-        let token_handler = TokenHandler::new();
-        let (consumer_key, pocket_send_url, access_token) = (
-            &self.configuration.consumer_key,
-            &self.configuration.pocket_send_url,
-            &token_handler.read_auth(),
-        );
-
-        let actions: serde_json::Value = articles
-            .into_iter()
+    /// Async version of [`API::archive`].
+    pub async fn archive_async(&self, articles: Vec<&Article>) -> Result<Vec<String>> {
+        let actions: Vec<Value> = articles
+            .iter()
             .map(|article| {
                 json!({
                     "action": ACTION_ARCHIVE,
@@ -232,27 +192,52 @@ impl API {
             })
             .collect();
 
+        let results = self.send_async(&actions).await?;
+
+        Ok(articles
+            .into_iter()
+            .zip(results)
+            .filter(|(_, archived)| *archived)
+            .map(|(article, _)| article.id.to_owned())
+            .collect())
+    }
+
+    /// Sends actions to `/v3/send`, returning whether Pocket applied each one.
+    pub async fn send_async(&self, actions: &[Value]) -> Result<Vec<bool>> {
+        if actions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let token_handler = TokenHandler::with_configuration(self.configuration.clone());
+        let (consumer_key, pocket_send_url, access_token) = (
+            &self.configuration.consumer_key,
+            &self.configuration.pocket_send_url,
+            &token_handler.read_auth()?,
+        );
+
         let params = [
             ("consumer_key", consumer_key),
             ("access_token", access_token),
-            ("actions", &actions.to_string()),
+            ("actions", &Value::from(actions.to_vec()).to_string()),
         ];
 
-        match reqwest::Client::new()
+        let response = reqwest::Client::new()
             .post(pocket_send_url)
             .form(&params)
             .send()
-            .await
-        {
-            Ok(_) => {
-                logger::log(&format!(
-                    "Successfully archived {} articles",
-                    actions.as_array().unwrap().len()
-                ));
-            }
-            Err(error) => {
-                logger::log(&error.to_string());
-            }
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(PickpocketError::from_response(&response));
         }
+
+        let text = response.text().await?;
+        let response: SendResponse = serde_json::from_str(&text)?;
+
+        // Actions without a result were not applied
+        let mut results = response.action_results;
+        results.resize(actions.len(), false);
+
+        Ok(results)
     }
 }
@@ -1,8 +1,190 @@
+use crate::articles::response::Item;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Average reading speed used to estimate reading times (see the
+/// `words_per_minute` setting).
+pub static WORDS_PER_MINUTE: u64 = 200;
+
+/// A single Pocket item, as stored on the local library.
+///
+/// Everything besides `id`, `url` and `title` is optional, so libraries written
+/// by older versions still load.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Article {
     pub id: String,
     pub url: String,
     pub title: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u64>,
+    /// Estimated reading time, in minutes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading_time: Option<u64>,
+    /// When the article was saved to Pocket, as a Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_added: Option<i64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// Site the article is on: the host of its URL, without `www.`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Publisher name Pocket knows the site by, like "The Verge".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_article: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub has_video: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub has_image: bool,
+    /// Until when the article is snoozed, as a Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<i64>,
+}
+
+impl Article {
+    /// Builds an article out of a Pocket item. Items without any URL are skipped.
+    pub fn from_item(id: &str, item: &Item) -> Option<Article> {
+        let url = item.url()?.to_owned();
+        let word_count = item.word_count.and_then(|count| u64::try_from(count).ok());
+
+        let mut tags: Vec<String> = item.tags.values().map(|tag| tag.tag.clone()).collect();
+        tags.sort();
+
+        let mut authors: Vec<String> = item
+            .authors
+            .values()
+            .map(|author| author.name.clone())
+            .filter(|name| !name.is_empty())
+            .collect();
+        authors.sort();
+
+        let resolved_url = item.resolved_url.clone().filter(|url| !url.is_empty());
+        let domain = Self::host(resolved_url.as_deref().unwrap_or(&url));
+        let publisher = item
+            .domain_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.name.clone())
+            .filter(|name| !name.is_empty());
+
+        Some(Article {
+            id: id.to_owned(),
+            title: item.title().to_owned(),
+            resolved_url,
+            excerpt: item.excerpt.clone().filter(|excerpt| !excerpt.is_empty()),
+            word_count,
+            reading_time: item
+                .time_to_read
+                .and_then(|minutes| u64::try_from(minutes).ok())
+                .filter(|minutes| *minutes > 0)
+                .or_else(|| word_count.map(Self::estimate_reading_time)),
+            time_added: item.time_added,
+            favorite: item.favorite,
+            tags,
+            authors,
+            domain,
+            publisher,
+            language: item.lang.clone().filter(|lang| !lang.is_empty()),
+            is_article: item.is_article,
+            has_video: item.has_video,
+            has_image: item.has_image,
+            snoozed_until: None,
+            url,
+        })
+    }
+
+    /// Minutes needed to read `word_count` words (at least one).
+    pub fn estimate_reading_time(word_count: u64) -> u64 {
+        Self::reading_time_at(word_count, WORDS_PER_MINUTE)
+    }
+
+    /// Minutes needed to read `word_count` words at `words_per_minute` (at least one).
+    pub fn reading_time_at(word_count: u64, words_per_minute: u64) -> u64 {
+        word_count.div_ceil(words_per_minute.max(1)).max(1)
+    }
+
+    /// Minutes needed to read the article at `words_per_minute`, falling back
+    /// to Pocket's estimate when the word count is unknown.
+    pub fn reading_minutes(&self, words_per_minute: u64) -> Option<u64> {
+        self.word_count
+            .map(|words| Self::reading_time_at(words, words_per_minute))
+            .or(self.reading_time)
+    }
+
+    /// Site the article is on, from its URL when `domain` is unknown.
+    pub fn site(&self) -> Option<String> {
+        self.domain
+            .clone()
+            .or_else(|| Self::host(self.resolved_url.as_deref().unwrap_or(&self.url)))
+    }
+
+    /// Host of `url`, without `www.`.
+    pub(crate) fn host(url: &str) -> Option<String> {
+        let url = url::Url::parse(url).ok()?;
+        let host = url.host_str()?;
+
+        Some(host.trim_start_matches("www.").to_owned())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::article::Article;
+    use crate::articles::response::Item;
+
+    #[test]
+    fn builds_articles_from_pocket_items() {
+        let item: Item = serde_json::from_str(
+            r#"{
+                "item_id": "1",
+                "given_url": "https://www.example.com/post",
+                "resolved_title": "A post",
+                "word_count": "1001",
+                "time_added": "1600000000",
+                "favorite": "1",
+                "tags": {"rust": {"tag": "rust"}, "cli": {"tag": "cli"}},
+                "authors": {"7": {"name": "Jane"}},
+                "lang": "en",
+                "domain_metadata": {"name": "Example Magazine"},
+                "is_article": "1",
+                "has_video": "2"
+            }"#,
+        )
+        .unwrap();
+
+        let article = Article::from_item("1", &item).unwrap();
+
+        assert_eq!(Some(6), article.reading_time);
+        assert_eq!(Some("example.com".to_owned()), article.domain);
+        assert_eq!(Some("Example Magazine".to_owned()), article.publisher);
+        assert_eq!(vec!["cli", "rust"], article.tags);
+        assert_eq!(vec!["Jane"], article.authors);
+        assert!(article.favorite && article.is_article && article.has_video);
+        assert!(!article.has_image);
+    }
+
+    #[test]
+    fn loads_articles_written_by_older_versions() {
+        let article: Article =
+            serde_yaml::from_str("id: \"1\"\nurl: https://example.com\ntitle: Old").unwrap();
+
+        assert_eq!("Old", article.title);
+        assert_eq!(None, article.word_count);
+        assert!(article.tags.is_empty());
+    }
 }
@@ -0,0 +1,150 @@
+use crate::articles::article::Article;
+use regex::Regex;
+
+/// Narrows down which unread articles [`crate::Library::pick`] may choose.
+///
+/// Every criterion has to match. Empty criteria match everything; articles
+/// with an unknown length never match a length criterion.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// The article must have one of these tags.
+    pub tags: Vec<String>,
+    /// The article must come from one of these domains (subdomains included).
+    pub domains: Vec<String>,
+    pub min_words: Option<u64>,
+    pub max_words: Option<u64>,
+    /// Longest reading time, in minutes, at the reading speed given to
+    /// [`Filter::matches`].
+    pub max_minutes: Option<u64>,
+    pub title_matches: Option<Regex>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Whether the filter lets every article through.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.domains.is_empty()
+            && self.min_words.is_none()
+            && self.max_words.is_none()
+            && self.max_minutes.is_none()
+            && self.title_matches.is_none()
+    }
+
+    /// Whether `article` matches, reading at `words_per_minute`.
+    pub fn matches(&self, article: &Article, words_per_minute: u64) -> bool {
+        self.matches_tags(article)
+            && self.matches_domains(article)
+            && self.matches_length(article, words_per_minute)
+            && self
+                .title_matches
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&article.title))
+    }
+
+    fn matches_tags(&self, article: &Article) -> bool {
+        self.tags.is_empty()
+            || article.tags.iter().any(|tag| {
+                self.tags
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(tag))
+            })
+    }
+
+    fn matches_domains(&self, article: &Article) -> bool {
+        if self.domains.is_empty() {
+            return true;
+        }
+
+        let site = match article.site() {
+            Some(site) => site.to_lowercase(),
+            None => return false,
+        };
+
+        self.domains.iter().any(|wanted| {
+            let wanted = wanted.trim_start_matches("www.").to_lowercase();
+            site == wanted || site.ends_with(&format!(".{}", wanted))
+        })
+    }
+
+    fn matches_length(&self, article: &Article, words_per_minute: u64) -> bool {
+        let words_match = match (self.min_words, self.max_words, article.word_count) {
+            (None, None, _) => true,
+            (_, _, None) => false,
+            (min, max, Some(words)) => {
+                min.is_none_or(|min| words >= min) && max.is_none_or(|max| words <= max)
+            }
+        };
+
+        let minutes_match = match (self.max_minutes, article.reading_minutes(words_per_minute)) {
+            (None, _) => true,
+            (Some(max), Some(minutes)) => minutes <= max,
+            (Some(_), None) => false,
+        };
+
+        words_match && minutes_match
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::article::{Article, WORDS_PER_MINUTE};
+    use crate::articles::filter::Filter;
+    use regex::Regex;
+
+    #[test]
+    fn matches_every_criterion() {
+        let article = Article {
+            id: "1".to_owned(),
+            url: "https://blog.example.com/post".to_owned(),
+            title: "Rust in production".to_owned(),
+            word_count: Some(1000),
+            reading_time: Some(5),
+            tags: vec!["rust".to_owned()],
+            ..Default::default()
+        };
+
+        assert!(Filter::new().is_empty());
+        assert!(Filter::new().matches(&article, WORDS_PER_MINUTE));
+
+        let filter = Filter {
+            tags: vec!["Rust".to_owned(), "go".to_owned()],
+            domains: vec!["www.example.com".to_owned()],
+            min_words: Some(500),
+            max_minutes: Some(5),
+            title_matches: Some(Regex::new("(?i)^rust").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&article, WORDS_PER_MINUTE));
+
+        let filter = Filter {
+            domains: vec!["ample.com".to_owned()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&article, WORDS_PER_MINUTE));
+
+        let filter = Filter {
+            max_words: Some(999),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&article, WORDS_PER_MINUTE));
+
+        let unknown_length = Article::default();
+        let filter = Filter {
+            max_minutes: Some(60),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&unknown_length, WORDS_PER_MINUTE));
+
+        // Reading times follow the reading speed
+        let filter = Filter {
+            max_minutes: Some(1),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&article, WORDS_PER_MINUTE));
+        assert!(filter.matches(&article, 1000));
+    }
+}
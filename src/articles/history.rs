@@ -0,0 +1,216 @@
+use crate::articles::article::Article;
+#[cfg(feature = "sqlite")]
+use crate::articles::sqlite::SqliteStore;
+#[cfg(feature = "sqlite")]
+use crate::articles::store::Storage;
+use crate::configuration::Configuration;
+use crate::crypto::{self, Cipher};
+use crate::error::Result;
+use crate::files::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// How a picked article was handed to the reader.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Opening {
+    /// Opened on the browser.
+    Browser,
+    /// Written to stdout.
+    Print,
+    /// Neither opened nor printed.
+    NotOpened,
+    /// Returned through the library API, left to the caller.
+    Library,
+}
+
+impl fmt::Display for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Opening::Browser => "browser",
+            Opening::Print => "print",
+            Opening::NotOpened => "not opened",
+            Opening::Library => "library",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single pick on the history journal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pick {
+    /// When the article was picked, as a Unix timestamp.
+    pub time: i64,
+    pub opening: Opening,
+    pub article: Article,
+    /// When the pick was undone, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undone: Option<i64>,
+    /// When an undo found the article already archived on Pocket, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<i64>,
+}
+
+/// Journal of every pick, oldest first. Stored as JSON lines, so recording a
+/// pick only appends to the file.
+#[derive(Debug, Default)]
+pub struct History {
+    picks: Vec<Pick>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn picks(&self) -> &[Pick] {
+        &self.picks
+    }
+
+    /// Picks made between `from` and `to` (Unix timestamps, both inclusive).
+    pub fn between(&self, from: Option<i64>, to: Option<i64>) -> Vec<&Pick> {
+        self.picks
+            .iter()
+            .filter(|pick| from.is_none_or(|from| pick.time >= from))
+            .filter(|pick| to.is_none_or(|to| pick.time <= to))
+            .collect()
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn from_picks(picks: Vec<Pick>) -> History {
+        History { picks }
+    }
+
+    /// Loads the history from disk (from the database with the `sqlite`
+    /// storage), returning an empty one if it does not exist.
+    pub fn load(config: &Configuration) -> Result<History> {
+        #[cfg(feature = "sqlite")]
+        if config.storage == Storage::Sqlite {
+            return SqliteStore::open(config)?.load_history();
+        }
+
+        let cipher = Cipher::new(config);
+        let history = History::load_file(&config.history_file, &cipher)?;
+        if cipher.needs_sealing(&config.history_file) {
+            history.save(config)?;
+        }
+        Ok(history)
+    }
+
+    /// Loads a history journal file, returning an empty history if it does not
+    /// exist.
+    pub(crate) fn load_file(path: &Path, cipher: &Cipher) -> Result<History> {
+        if !path.exists() {
+            return Ok(History::new());
+        }
+
+        let content = cipher.read_to_string(path)?;
+        let picks = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<Vec<Pick>, _>>()?;
+
+        Ok(History { picks })
+    }
+
+    pub fn save(&self, config: &Configuration) -> Result<()> {
+        #[cfg(feature = "sqlite")]
+        if config.storage == Storage::Sqlite {
+            return SqliteStore::open(config)?.save_history(self);
+        }
+
+        let mut content = String::new();
+        for pick in &self.picks {
+            content.push_str(&serde_json::to_string(pick)?);
+            content.push('\n');
+        }
+
+        let cipher = Cipher::new(config);
+        write_atomically(&config.history_file, cipher.seal(content.as_bytes())?)?;
+        Ok(())
+    }
+
+    /// Appends picks to the journal on disk, without loading it. Encrypted
+    /// journals can't be appended to, so they are written whole instead.
+    pub fn record(config: &Configuration, picks: &[Pick]) -> Result<()> {
+        #[cfg(feature = "sqlite")]
+        if config.storage == Storage::Sqlite {
+            return SqliteStore::open(config)?.record(picks);
+        }
+
+        if config.encryption || crypto::is_encrypted(&config.history_file) {
+            let mut history = History::load_file(&config.history_file, &Cipher::new(config))?;
+            history.picks.extend_from_slice(picks);
+            return history.save(config);
+        }
+
+        let mut lines = String::new();
+        for pick in picks {
+            lines.push_str(&serde_json::to_string(pick)?);
+            lines.push('\n');
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.history_file)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    /// The last `count` picks neither undone nor found archived yet, most
+    /// recent first.
+    pub fn last_mut(&mut self, count: usize) -> Vec<&mut Pick> {
+        self.picks
+            .iter_mut()
+            .rev()
+            .filter(|pick| pick.undone.is_none() && pick.archived.is_none())
+            .take(count)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::article::Article;
+    use crate::articles::history::{History, Opening, Pick};
+
+    #[test]
+    fn filters_picks_by_date_and_skips_undone_ones() {
+        let pick = |id: &str, time: i64, undone: Option<i64>| Pick {
+            time,
+            opening: Opening::Browser,
+            article: Article {
+                id: id.to_owned(),
+                ..Default::default()
+            },
+            undone,
+            archived: None,
+        };
+        let mut history = History {
+            picks: vec![
+                pick("1", 10, None),
+                pick("2", 20, Some(25)),
+                pick("3", 30, None),
+            ],
+        };
+
+        let between: Vec<&str> = history
+            .between(Some(15), Some(30))
+            .iter()
+            .map(|pick| pick.article.id.as_str())
+            .collect();
+        assert_eq!(vec!["2", "3"], between);
+
+        let last: Vec<String> = history
+            .last_mut(2)
+            .iter()
+            .map(|pick| pick.article.id.clone())
+            .collect();
+        assert_eq!(vec!["3", "1"], last);
+    }
+}
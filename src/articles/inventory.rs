@@ -2,7 +2,8 @@ use crate::articles::article::Article;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A set of articles, keyed by their Pocket item id.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Inventory {
     pub articles: HashMap<String, Article>,
 }
@@ -13,4 +14,8 @@ impl Inventory {
             articles: HashMap::new(),
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.articles.is_empty()
+    }
 }
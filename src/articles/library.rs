@@ -1,169 +1,896 @@
 use crate::articles::api::API;
 use crate::articles::article::Article;
+use crate::articles::filter::Filter;
+use crate::articles::history::{History, Opening, Pick};
 use crate::articles::inventory::Inventory;
+use crate::articles::migration::{self, MigrationReport, SCHEMA_VERSION, UNVERSIONED};
+use crate::articles::outbox::{now, Action, Outbox};
+use crate::articles::response::ItemStatus;
+use crate::articles::snapshot::Snapshot;
+use crate::articles::store::LibraryStore;
+use crate::articles::strategy::Strategy;
 use crate::configuration::Configuration;
-use crate::logger;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
 use rand::seq::SliceRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serde_yaml;
-use std::collections::HashMap;
-use std::fs::File;
-use std::path::Path;
+use std::collections::HashSet;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Random sets drawn by [`Library::pick_budget`], keeping the one closest to the budget.
+static BUDGET_TRIALS: usize = 32;
+
+/// The local library: articles still to be read, articles already picked, and
+/// articles snoozed until a later date.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Library {
+    /// Layout version, upgraded on load (see [`crate::articles::migration`]).
+    #[serde(default = "unversioned")]
+    schema_version: u32,
     read: Inventory,
     unread: Inventory,
+    #[serde(default, skip_serializing_if = "Inventory::is_empty")]
+    snoozed: Inventory,
+    /// Pocket's timestamp of the last sync, used to only retrieve what changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    since: Option<i64>,
+    /// Order unread articles come up in with the shuffle bag strategy.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bag: Vec<String>,
+}
+
+/// Number of read/unread/snoozed articles on the local library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub read: usize,
+    pub unread: usize,
+    pub snoozed: usize,
+}
+
+/// Outcome of a [`Library::undo`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Undo {
+    /// Articles moved back to unread.
+    pub restored: Vec<Article>,
+    /// Picked articles already archived on Pocket, which can't be undone.
+    pub archived: Vec<Article>,
+}
+
+/// Outcome of a [`Library::renew`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenewSummary {
+    /// Read articles Pocket confirmed archiving.
+    pub archived: usize,
+    /// Read articles still waiting to be archived.
+    pub pending: usize,
+    /// Outbox actions Pocket applied (archives included).
+    pub sent: usize,
+    /// Outbox actions given up on after too many attempts.
+    pub failed: usize,
+    /// Articles added or updated.
+    pub retrieved: usize,
+    /// Articles archived or deleted on Pocket, dropped from the library.
+    pub removed: usize,
+}
+
+fn unversioned() -> u32 {
+    UNVERSIONED
+}
+
+impl Default for Library {
+    fn default() -> Self {
+        Library::new()
+    }
 }
 
 impl Library {
     pub fn new() -> Library {
         Library {
+            schema_version: SCHEMA_VERSION,
             read: Inventory::new(),
             unread: Inventory::new(),
+            snoozed: Inventory::new(),
+            since: None,
+            bag: Vec::new(),
         }
     }
 
-    pub fn guarantee_home_folder() {
-        let config = Configuration::default();
-        match std::fs::create_dir_all(config.home_folder) {
-            Ok(_) => {}
-            Err(error) => {
-                let message = format!("Could not create home folder. Motive: {}", error);
-                logger::log(&message);
-            }
-        };
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version
     }
 
-    fn write_inventory(library: &Library) {
-        let config = Configuration::default();
-        let library_string = serde_yaml::to_string(library).unwrap();
+    pub fn read(&self) -> &Inventory {
+        &self.read
+    }
 
-        std::fs::write(config.library_file, library_string).ok();
+    pub fn unread(&self) -> &Inventory {
+        &self.unread
     }
 
-    fn load() -> Library {
-        let config = Configuration::default();
+    pub fn snoozed(&self) -> &Inventory {
+        &self.snoozed
+    }
 
-        if !Path::new(&config.library_file).exists() {
-            logger::log("Inventory file not found. Creating...");
-            Library::write_inventory(&Library::new());
-            File::open(&config.library_file).unwrap();
+    pub fn since(&self) -> Option<i64> {
+        self.since
+    }
+
+    /// Order unread articles come up in with the shuffle bag strategy.
+    pub fn bag(&self) -> &[String] {
+        &self.bag
+    }
+
+    /// Puts together a library read by a [`crate::LibraryStore`].
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn from_parts(
+        read: Inventory,
+        unread: Inventory,
+        snoozed: Inventory,
+        since: Option<i64>,
+        bag: Vec<String>,
+    ) -> Library {
+        Library {
+            schema_version: SCHEMA_VERSION,
+            read,
+            unread,
+            snoozed,
+            since,
+            bag,
         }
+    }
 
-        let content = std::fs::read_to_string(config.library_file).unwrap();
-        serde_yaml::from_str::<Library>(&content).unwrap()
+    pub fn guarantee_home_folder(config: &Configuration) -> Result<()> {
+        std::fs::create_dir_all(&config.home_folder)?;
+        Ok(())
     }
 
-    fn random_unread_article() -> Option<Article> {
-        let library = Library::load();
-        let article_ids: Vec<&String> = library.unread.articles.keys().collect();
-        let mut rng = rand::thread_rng();
-        let choice = article_ids.choose(&mut rng);
+    /// Loads the library from the configured store, creating an empty one if
+    /// it does not exist. Snoozed articles whose date has passed are back to
+    /// unread.
+    pub fn load(config: &Configuration) -> Result<Library> {
+        let mut library = config.storage.open(config)?.load()?;
+        library.wake(now());
 
-        match choice {
-            Some(article_id) => {
-                let id = article_id.to_string();
-                let article = &library.unread.articles[&id];
+        Ok(library)
+    }
 
-                Some(article.to_owned())
-            }
-            None => None,
+    /// Upgrades the library file to [`SCHEMA_VERSION`], keeping a backup of
+    /// it. With `check`, only reports what would change. Libraries are
+    /// upgraded on load anyway; this makes it explicit.
+    pub fn migrate(config: &Configuration, check: bool) -> Result<MigrationReport> {
+        let library = match config.storage.read_raw(config)? {
+            Some(library) => library,
+            None => return Ok(MigrationReport::up_to_date()),
+        };
+
+        if check {
+            let mut library = library;
+            return migration::upgrade(&mut library);
+        }
+        if migration::version(&library) == SCHEMA_VERSION {
+            return Ok(MigrationReport::up_to_date());
         }
+
+        let store = config.storage.open(config)?;
+        let (_, report) = migration::upgrade_file(
+            &config.library_file,
+            library,
+            &Cipher::new(config),
+            |library| store.save(library),
+        )?;
+        Ok(report)
     }
 
-    fn move_to_read(article_id: String) {
-        let mut library = Library::load();
+    fn wake(&mut self, now: i64) {
+        let due: Vec<String> = self
+            .snoozed
+            .articles
+            .values()
+            .filter(|article| article.snoozed_until.is_none_or(|until| until <= now))
+            .map(|article| article.id.to_owned())
+            .collect();
 
-        match library.unread.articles.remove(&article_id) {
-            Some(read_article) => {
-                library
-                    .read
-                    .articles
-                    .insert(read_article.id.to_owned(), read_article.to_owned());
+        for id in due {
+            if let Some(mut article) = self.snoozed.articles.remove(&id) {
+                article.snoozed_until = None;
+                self.unread.articles.insert(id, article);
             }
-            None => {}
-        };
+        }
+    }
+
+    pub fn status(config: &Configuration) -> Result<Status> {
+        let library = Library::load(config)?;
+
+        Ok(Status {
+            read: library.read.articles.len(),
+            unread: library.unread.articles.len(),
+            snoozed: library.snoozed.articles.len(),
+        })
+    }
+
+    /// Picks up to `quantity` (default: 1) unread articles following the
+    /// configured [`Strategy`](crate::Strategy), marking them
+    /// as read and queueing their archiving on the outbox. Fewer articles are
+    /// returned once every article has been read.
+    ///
+    /// Every pick is recorded on the [`History`].
+    pub fn pick(config: &Configuration, quantity: Option<usize>) -> Result<Vec<Article>> {
+        Library::pick_with_rng(
+            config,
+            quantity,
+            &Filter::new(),
+            Opening::Library,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Same as [`Library::pick`], only choosing among articles matching
+    /// `filter` and drawing from `rng`. Given the same library and a seeded
+    /// RNG (e.g. `StdRng::seed_from_u64`), the same articles are picked.
+    /// `opening` tells the [`History`] how the caller hands them to the reader.
+    ///
+    /// Nothing is marked as read when no article matches.
+    ///
+    /// The library is saved first, then the archiving is queued on the
+    /// outbox and the picks recorded on the history, each with its own write.
+    /// Should a command stop in between, the articles stay read: the next
+    /// [`Library::renew`] archives read articles missing from the outbox, but
+    /// the picks are missing from the history.
+    pub fn pick_with_rng<R: Rng + ?Sized>(
+        config: &Configuration,
+        quantity: Option<usize>,
+        filter: &Filter,
+        opening: Opening,
+        rng: &mut R,
+    ) -> Result<Vec<Article>> {
+        let picked = Library::transaction(config, |library| {
+            let picked = library.draw(config, quantity, filter, rng);
+            library.mark_read(&picked);
+            Ok(picked)
+        })?;
+
+        Library::journal(config, &picked, opening)?;
+        Ok(picked)
+    }
+
+    /// Chooses the articles [`Library::pick_with_rng`] would pick, without
+    /// changing the library.
+    pub fn draw_with_rng<R: Rng + ?Sized>(
+        config: &Configuration,
+        quantity: Option<usize>,
+        filter: &Filter,
+        rng: &mut R,
+    ) -> Result<Vec<Article>> {
+        Ok(Library::load(config)?.draw(config, quantity, filter, rng))
+    }
 
-        Library::write_inventory(&library);
+    /// Picks a random set of unread articles whose total reading time (at the
+    /// configured `words_per_minute`) fits in `minutes` as closely as possible,
+    /// marking them as read and queueing their archiving on the outbox.
+    pub fn pick_budget(config: &Configuration, minutes: u64) -> Result<Vec<Article>> {
+        Library::pick_budget_with_rng(
+            config,
+            minutes,
+            &Filter::new(),
+            Opening::Library,
+            &mut rand::thread_rng(),
+        )
     }
 
-    pub fn status() {
-        let library = Library::load();
+    /// Same as [`Library::pick_budget`], only choosing among articles matching
+    /// `filter` and drawing from `rng`. Articles of unknown length are left out.
+    pub fn pick_budget_with_rng<R: Rng + ?Sized>(
+        config: &Configuration,
+        minutes: u64,
+        filter: &Filter,
+        opening: Opening,
+        rng: &mut R,
+    ) -> Result<Vec<Article>> {
+        let picked = Library::transaction(config, |library| {
+            let picked = library.draw_budget(config, minutes, filter, rng);
+            library.mark_read(&picked);
+            Ok(picked)
+        })?;
 
-        logger::log(&format!(
-            "You have {} read articles",
-            &library.read.articles.len()
-        ));
-        logger::log(&format!(
-            "You have {} unread articles",
-            &library.unread.articles.len()
-        ));
+        Library::journal(config, &picked, opening)?;
+        Ok(picked)
     }
 
-    pub fn pick(quantity: Option<usize>) {
+    /// Chooses the articles [`Library::pick_budget_with_rng`] would pick,
+    /// without changing the library.
+    pub fn draw_budget_with_rng<R: Rng + ?Sized>(
+        config: &Configuration,
+        minutes: u64,
+        filter: &Filter,
+        rng: &mut R,
+    ) -> Result<Vec<Article>> {
+        Ok(Library::load(config)?.draw_budget(config, minutes, filter, rng))
+    }
+
+    /// Unread articles matching `filter`, in shuffle bag order with the
+    /// shuffle bag strategy, otherwise sorted so the same library and seed
+    /// always give the same picks.
+    fn candidates<R: Rng + ?Sized>(
+        &mut self,
+        config: &Configuration,
+        filter: &Filter,
+        rng: &mut R,
+    ) -> Vec<&Article> {
+        if config.strategy == Strategy::ShuffleBag {
+            self.refill_bag(rng);
+
+            let unread = &self.unread.articles;
+            return self
+                .bag
+                .iter()
+                .map(|id| &unread[id])
+                .filter(|article| filter.matches(article, config.words_per_minute))
+                .collect();
+        }
+
+        let mut candidates: Vec<&Article> = self
+            .unread
+            .articles
+            .values()
+            .filter(|article| filter.matches(article, config.words_per_minute))
+            .collect();
+        candidates.sort_by(|a, b| a.id.cmp(&b.id));
+        candidates
+    }
+
+    /// Brings the shuffle bag in line with the unread inventory: articles no
+    /// longer unread leave it, new ones slip in at random positions.
+    fn refill_bag<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let unread = &self.unread.articles;
+        self.bag.retain(|id| unread.contains_key(id));
+
+        let bagged: HashSet<&String> = self.bag.iter().collect();
+        let mut new: Vec<String> = unread
+            .keys()
+            .filter(|id| !bagged.contains(id))
+            .cloned()
+            .collect();
+        // Sorted first, so the same seed always gives the same bag
+        new.sort();
+
+        if self.bag.is_empty() {
+            new.shuffle(rng);
+            self.bag = new;
+            return;
+        }
+
+        for id in new {
+            let position = rng.gen_range(0, self.bag.len() + 1);
+            self.bag.insert(position, id);
+        }
+    }
+
+    fn draw<R: Rng + ?Sized>(
+        &mut self,
+        config: &Configuration,
+        quantity: Option<usize>,
+        filter: &Filter,
+        rng: &mut R,
+    ) -> Vec<Article> {
         let quantity = quantity.unwrap_or(1);
+        let now = now();
+        let mut candidates = self.candidates(config, filter, rng);
+        let mut drawn = Vec::new();
+        let mut used = HashSet::new();
+
+        while drawn.len() < quantity {
+            let choice = match config.diversity {
+                Some(diversity) => {
+                    let mut fresh: Vec<&Article> = candidates
+                        .iter()
+                        .filter(|article| diversity.is_fresh(article, &used))
+                        .copied()
+                        .collect();
 
-        for _ in 0..quantity {
-            match Library::random_unread_article() {
+                    // Every group was picked: start another round
+                    if fresh.is_empty() {
+                        used.clear();
+                        fresh = candidates.clone();
+                    }
+
+                    let choice = config.strategy.choose(
+                        &fresh,
+                        &config.weights,
+                        now,
+                        config.words_per_minute,
+                        rng,
+                    );
+                    if let Some(article) = choice {
+                        used.extend(diversity.groups(article));
+                    }
+                    choice
+                }
+                None => config.strategy.choose(
+                    &candidates,
+                    &config.weights,
+                    now,
+                    config.words_per_minute,
+                    rng,
+                ),
+            };
+
+            match choice {
                 Some(article) => {
-                    Library::move_to_read(article.id);
-                    open::that(article.url).ok();
+                    candidates.retain(|candidate| candidate.id != article.id);
+                    drawn.push(article.clone());
                 }
-                None => {
-                    logger::log("You have read all articles!");
+                None => break,
+            }
+        }
+
+        drawn
+    }
+
+    fn draw_budget<R: Rng + ?Sized>(
+        &mut self,
+        config: &Configuration,
+        minutes: u64,
+        filter: &Filter,
+        rng: &mut R,
+    ) -> Vec<Article> {
+        let now = now();
+        let length = |article: &Article| article.reading_minutes(config.words_per_minute);
+
+        let mut candidates = self.candidates(config, filter, rng);
+        candidates.retain(|article| length(article).is_some_and(|length| length <= minutes));
+
+        // Fill the budget in the strategy's order, keeping the fullest set.
+        // Diverse picks first take articles from groups not picked yet.
+        let passes = if config.diversity.is_some() { 2 } else { 1 };
+        let trials = if config.strategy.is_random() {
+            BUDGET_TRIALS
+        } else {
+            1
+        };
+        let mut best: (u64, Vec<&Article>) = (0, Vec::new());
+
+        for _ in 0..trials {
+            let order = config.strategy.order(
+                &candidates,
+                &config.weights,
+                now,
+                config.words_per_minute,
+                rng,
+            );
+            let mut taken = vec![false; order.len()];
+            let mut used = HashSet::new();
+            let mut total = 0;
+            let mut set = Vec::new();
+
+            for pass in 0..passes {
+                for (index, article) in order.iter().enumerate() {
+                    let fresh = match config.diversity {
+                        Some(diversity) if pass == 0 => diversity.is_fresh(article, &used),
+                        _ => true,
+                    };
+                    let length = length(article).unwrap_or_default();
+
+                    if !taken[index] && fresh && total + length <= minutes {
+                        total += length;
+                        taken[index] = true;
+                        set.push(*article);
+                        if let Some(diversity) = config.diversity {
+                            used.extend(diversity.groups(article));
+                        }
+                    }
                 }
-            };
+            }
+
+            if total > best.0 {
+                best = (total, set);
+            }
+            if best.0 == minutes {
+                break;
+            }
         }
+
+        best.1.into_iter().cloned().collect()
     }
 
-    pub fn renew() {
-        let api = API::new();
-        let library = Library::load();
+    /// Snoozes an article until `until` (a Unix timestamp): picks ignore it
+    /// until then. A picked article goes back to unread once the date passes,
+    /// its archiving cancelled.
+    pub fn snooze(config: &Configuration, article_id: &str, until: i64) -> Result<Article> {
+        let article = Library::transaction(config, |library| {
+            let mut article = library.remove(article_id)?;
 
-        // Archive read articles in Pocket
-        let read_articles: Vec<&Article> = library.read.articles.values().collect();
-        api.archive(read_articles);
+            article.snoozed_until = Some(until);
+            library
+                .snoozed
+                .articles
+                .insert(article.id.to_owned(), article.clone());
+            Ok(article)
+        })?;
 
-        // Retrieve new articles from Pocket
-        let api_list = api.retrieve()["list"].to_owned();
-        let api_articles =
-            match serde_json::from_value::<HashMap<String, serde_json::Value>>(api_list) {
-                Ok(articles) => articles,
-                Err(_) => HashMap::new(),
-            };
+        let mut outbox = Outbox::load(config)?;
+        if outbox.cancel(&Action::Archive {
+            item_id: article.id.to_owned(),
+        }) {
+            outbox.save(config)?;
+        }
+
+        Ok(article)
+    }
+
+    /// Moves the last `count` picks back to unread, cancelling their archiving.
+    /// Picks already archived on Pocket (and dropped by a renew) are left
+    /// alone, and marked so that later undos skip them.
+    pub fn undo(config: &Configuration, count: usize) -> Result<Undo> {
+        let mut history = History::load(config)?;
+        let mut outbox = Outbox::load(config)?;
+        let mut undo = Undo::default();
+        let now = now();
+
+        Library::transaction(config, |library| {
+            for pick in history.last_mut(count) {
+                let article = library
+                    .read
+                    .articles
+                    .remove(&pick.article.id)
+                    .or_else(|| library.snoozed.articles.remove(&pick.article.id));
+
+                match article {
+                    Some(mut article) => {
+                        article.snoozed_until = None;
+                        outbox.cancel(&Action::Archive {
+                            item_id: article.id.to_owned(),
+                        });
+                        pick.undone = Some(now);
+                        library
+                            .unread
+                            .articles
+                            .insert(article.id.to_owned(), article.clone());
+                        undo.restored.push(article);
+                    }
+                    None => {
+                        // Reported once: later undos go past it
+                        pick.archived = Some(now);
+                        undo.archived.push(pick.article.clone());
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        outbox.save(config)?;
+        history.save(config)?;
+
+        Ok(undo)
+    }
+
+    /// Moves `picked` to the read inventory.
+    fn mark_read(&mut self, picked: &[Article]) {
+        for article in picked {
+            if let Some(article) = self.unread.articles.remove(&article.id) {
+                self.read.articles.insert(article.id.to_owned(), article);
+            }
+        }
 
-        let new_inventory: HashMap<String, Article> = api_articles
-            .into_iter()
-            .map(|(id, data)| {
-                let resolved_title = data["resolved_title"].as_str();
-                let given_title = data["given_title"].as_str();
-
-                let title = match resolved_title {
-                    Some(title) => title,
-                    None => given_title.unwrap_or(""),
-                };
-
-                (
-                    id.to_string(),
-                    Article {
-                        id: id.to_owned(),
-                        url: data["given_url"].as_str().unwrap().to_owned(),
-                        title: title.to_owned(),
-                    },
-                )
+        let picked: HashSet<&str> = picked.iter().map(|article| article.id.as_str()).collect();
+        self.bag.retain(|id| !picked.contains(id.as_str()));
+    }
+
+    /// Queues the archiving of `picked` on the outbox and records them on the
+    /// history, once the library saved them as read (see
+    /// [`Library::pick_with_rng`] for what this guarantees).
+    fn journal(config: &Configuration, picked: &[Article], opening: Opening) -> Result<()> {
+        let mut outbox = Outbox::load(config)?;
+        for article in picked {
+            outbox.push(Action::Archive {
+                item_id: article.id.to_owned(),
+            });
+        }
+        outbox.save(config)?;
+
+        let time = now();
+        let picks: Vec<Pick> = picked
+            .iter()
+            .map(|article| Pick {
+                time,
+                opening,
+                article: article.clone(),
+                undone: None,
+                archived: None,
             })
             .collect();
+        History::record(config, &picks)
+    }
 
-        // Create new Library
-        let new_library = Library {
-            read: Inventory::new(),
-            unread: Inventory {
-                articles: new_inventory,
+    /// Marks an article as favorite, queueing the change on the outbox.
+    pub fn favorite(config: &Configuration, article_id: &str) -> Result<Article> {
+        let article =
+            Library::update_article(config, article_id, |article| article.favorite = true)?;
+        Library::queue(
+            config,
+            Action::Favorite {
+                item_id: article_id.to_owned(),
             },
-        };
+        )?;
+        Ok(article)
+    }
+
+    /// Adds tags to an article, queueing the change on the outbox.
+    pub fn tag(config: &Configuration, article_id: &str, tags: &[String]) -> Result<Article> {
+        let article = Library::update_article(config, article_id, |article| {
+            for tag in tags {
+                if !article.tags.contains(tag) {
+                    article.tags.push(tag.to_owned());
+                }
+            }
+            article.tags.sort();
+        })?;
+        Library::queue(
+            config,
+            Action::TagsAdd {
+                item_id: article_id.to_owned(),
+                tags: tags.to_vec(),
+            },
+        )?;
+        Ok(article)
+    }
+
+    /// Removes an article from the library, queueing its deletion on the
+    /// outbox. A [`Snapshot`] of the library is taken first.
+    pub fn delete(config: &Configuration, article_id: &str) -> Result<Article> {
+        let article = Library::transaction(config, |library| {
+            if library.contains(article_id) {
+                Snapshot::take(config, "delete", library)?;
+            }
+            library.remove(article_id)
+        })?;
+        Library::queue(
+            config,
+            Action::Delete {
+                item_id: article_id.to_owned(),
+            },
+        )?;
+
+        Ok(article)
+    }
+
+    /// Queues a new URL to be saved on Pocket. It joins the library on the next renew.
+    pub fn add(config: &Configuration, url: &str, title: Option<&str>) -> Result<()> {
+        if url::Url::parse(url).is_err() {
+            return Err(PickpocketError::InvalidArgument(format!(
+                "Invalid URL: {}",
+                url
+            )));
+        }
+
+        Library::queue(
+            config,
+            Action::Add {
+                url: url.to_owned(),
+                title: title.map(str::to_owned),
+            },
+        )
+    }
+
+    fn contains(&self, article_id: &str) -> bool {
+        self.unread.articles.contains_key(article_id)
+            || self.read.articles.contains_key(article_id)
+            || self.snoozed.articles.contains_key(article_id)
+    }
+
+    /// Takes an article out of whichever inventory holds it.
+    fn remove(&mut self, article_id: &str) -> Result<Article> {
+        self.unread
+            .articles
+            .remove(article_id)
+            .or_else(|| self.read.articles.remove(article_id))
+            .or_else(|| self.snoozed.articles.remove(article_id))
+            .ok_or_else(|| Library::not_found(article_id))
+    }
+
+    /// Applies `update` to an article, wherever it is, returning it updated.
+    fn update_article<F: FnOnce(&mut Article)>(
+        config: &Configuration,
+        article_id: &str,
+        update: F,
+    ) -> Result<Article> {
+        Library::transaction(config, |library| {
+            let Library {
+                read,
+                unread,
+                snoozed,
+                ..
+            } = library;
+            let article = unread
+                .articles
+                .get_mut(article_id)
+                .or_else(|| read.articles.get_mut(article_id))
+                .or_else(|| snoozed.articles.get_mut(article_id))
+                .ok_or_else(|| Library::not_found(article_id))?;
+
+            update(article);
+            Ok(article.clone())
+        })
+    }
+
+    /// Loads the library once from the configured store, applies `change` and
+    /// saves the outcome once. Nothing is saved when `change` fails.
+    fn transaction<T, F: FnOnce(&mut Library) -> Result<T>>(
+        config: &Configuration,
+        change: F,
+    ) -> Result<T> {
+        Library::apply(config.storage.open(config)?.as_ref(), change)
+    }
+
+    /// Runs `change` through `store`'s update, failing if the store never
+    /// applied it.
+    fn apply<T, F: FnOnce(&mut Library) -> Result<T>>(
+        store: &dyn LibraryStore,
+        change: F,
+    ) -> Result<T> {
+        let mut change = Some(change);
+        let mut outcome = None;
+
+        store.update(&mut |library| {
+            library.wake(now());
+            if let Some(change) = change.take() {
+                outcome = Some(change(library)?);
+            }
+            Ok(())
+        })?;
+
+        outcome.ok_or_else(|| {
+            PickpocketError::Storage("the library update did not apply the change".to_owned())
+        })
+    }
+
+    fn queue(config: &Configuration, action: Action) -> Result<()> {
+        let mut outbox = Outbox::load(config)?;
+        outbox.push(action);
+        outbox.save(config)
+    }
+
+    fn not_found(article_id: &str) -> PickpocketError {
+        PickpocketError::InvalidArgument(format!("Article {} is not on your library", article_id))
+    }
+
+    /// Flushes the outbox (archiving read articles) and syncs the unread
+    /// inventory with Pocket. The first sync downloads every unread article,
+    /// later ones only what changed since the previous sync.
+    ///
+    /// Read articles are only dropped once Pocket confirms archiving them; the
+    /// others stay pending for the next renew. The unread inventory is left
+    /// alone when retrieval fails or a full sync comes back empty.
+    ///
+    /// What Pocket answers is merged into the library in a single store
+    /// transaction, once every request is done, so library changes made
+    /// meanwhile are kept. A [`Snapshot`] of the library is taken first.
+    pub fn renew(config: &Configuration) -> Result<RenewSummary> {
+        let api = API::with_configuration(config.clone());
+        let library = Library::load(config)?;
+        Snapshot::take(config, "renew", &library)?;
+        let mut outbox = Outbox::load(config)?;
+
+        // Read articles from older versions never went through the outbox
+        for id in library.read.articles.keys() {
+            let archive = Action::Archive {
+                item_id: id.to_owned(),
+            };
+            if !outbox.contains(&archive) {
+                outbox.push(archive);
+            }
+        }
+
+        // Send local changes to Pocket
+        let flushed = outbox.flush(&api, config.max_attempts)?;
+        outbox.save(config)?;
+
+        // Retrieve new articles from Pocket, keeping the archive progress if it fails
+        let since = library.since;
+        let response = api.retrieve_since(since);
+
+        let summary = Library::transaction(config, |library| {
+            let mut summary = RenewSummary {
+                archived: 0,
+                pending: 0,
+                sent: flushed.sent.len(),
+                failed: flushed.failed.len(),
+                retrieved: 0,
+                removed: 0,
+            };
+
+            for action in &flushed.sent {
+                if let Action::Archive { item_id } = action {
+                    if library.read.articles.remove(item_id).is_some() {
+                        summary.archived += 1;
+                    }
+                }
+            }
+            summary.pending = library.read.articles.len();
+
+            let response = match &response {
+                Ok(response) => response,
+                Err(_) => return Ok(summary),
+            };
+
+            if since.is_none() {
+                if response.list.is_empty() && !library.unread.articles.is_empty() {
+                    // Keep the local articles, and do a full sync again next time
+                    return Ok(summary);
+                }
+
+                // A full sync replaces the unread inventory
+                summary.removed = library
+                    .unread
+                    .articles
+                    .keys()
+                    .filter(|id| !response.list.contains_key(*id))
+                    .count();
+                library.unread = Inventory::new();
+            }
+
+            for (id, item) in &response.list {
+                match item.status {
+                    // Articles pending archiving stay read, snoozed ones stay
+                    // snoozed and pending deletions stay gone
+                    ItemStatus::Unread if library.read.articles.contains_key(id) => {}
+                    ItemStatus::Unread if library.snoozed.articles.contains_key(id) => {}
+                    ItemStatus::Unread
+                        if outbox.contains(&Action::Delete {
+                            item_id: id.clone(),
+                        }) => {}
+                    ItemStatus::Unread => {
+                        if let Some(article) = Article::from_item(id, item) {
+                            library.unread.articles.insert(id.clone(), article);
+                            summary.retrieved += 1;
+                        }
+                    }
+                    ItemStatus::Archived | ItemStatus::Deleted => {
+                        library.read.articles.remove(id);
+                        let snoozed = library.snoozed.articles.remove(id);
+                        if library.unread.articles.remove(id).or(snoozed).is_some() {
+                            summary.removed += 1;
+                        }
+                    }
+                }
+            }
+
+            summary.pending = library.read.articles.len();
+            library.since = response.since;
+            Ok(summary)
+        })?;
+
+        response?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::library::Library;
+    use crate::articles::store::LibraryStore;
+    use crate::error::{PickpocketError, Result};
+
+    /// A store whose updates never run the change.
+    struct ForgetfulStore;
+
+    impl LibraryStore for ForgetfulStore {
+        fn load(&self) -> Result<Library> {
+            Ok(Library::new())
+        }
+
+        fn save(&self, _library: &Library) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&self, _change: &mut dyn FnMut(&mut Library) -> Result<()>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fails_when_the_store_does_not_apply_the_change() {
+        let outcome = Library::apply(&ForgetfulStore, |_library| Ok(1));
 
-        Library::write_inventory(&new_library);
-        logger::log("Refreshed library");
+        assert!(matches!(outcome, Err(PickpocketError::Storage(_))));
     }
 }
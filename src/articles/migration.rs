@@ -0,0 +1,183 @@
+use crate::articles::article::Article;
+use crate::articles::library::Library;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Layout version of the libraries this release writes.
+pub static SCHEMA_VERSION: u32 = 2;
+
+/// Version of library files written before they carried a `schema_version`.
+pub static UNVERSIONED: u32 = 1;
+
+/// Upgrades a raw library from the version it is indexed at to the next one,
+/// describing each change it makes.
+type Step = fn(&mut Value) -> Vec<String>;
+
+/// Every upgrade step, the first one upgrading from [`UNVERSIONED`].
+static STEPS: [Step; 1] = [add_domains];
+
+/// What upgrading a library to [`SCHEMA_VERSION`] changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    pub changes: Vec<String>,
+    /// Copy of the library file taken before upgrading it, if it was.
+    pub backup: Option<PathBuf>,
+}
+
+impl MigrationReport {
+    /// The report of a library already at [`SCHEMA_VERSION`].
+    pub fn up_to_date() -> MigrationReport {
+        MigrationReport {
+            from: SCHEMA_VERSION,
+            to: SCHEMA_VERSION,
+            changes: Vec::new(),
+            backup: None,
+        }
+    }
+
+    pub fn is_needed(&self) -> bool {
+        self.from < self.to
+    }
+}
+
+/// Schema version of a raw library.
+pub fn version(library: &Value) -> u32 {
+    library
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or(UNVERSIONED, |version| version as u32)
+}
+
+/// Runs the upgrade steps a raw library needs to reach [`SCHEMA_VERSION`].
+/// Libraries written by a newer release are refused.
+pub fn upgrade(library: &mut Value) -> Result<MigrationReport> {
+    let from = version(library);
+    if from > SCHEMA_VERSION {
+        return Err(PickpocketError::UnsupportedSchema(from));
+    }
+
+    let mut report = MigrationReport {
+        from,
+        ..MigrationReport::up_to_date()
+    };
+    if !report.is_needed() {
+        return Ok(report);
+    }
+
+    for step in &STEPS[(from - UNVERSIONED) as usize..] {
+        report.changes.extend(step(library));
+    }
+    if let Some(library) = library.as_object_mut() {
+        library.insert("schema_version".to_owned(), SCHEMA_VERSION.into());
+    }
+    report
+        .changes
+        .push(format!("Set schema_version to {}", SCHEMA_VERSION));
+
+    Ok(report)
+}
+
+/// Upgrades the raw library read from the file at `path`, copying the file
+/// aside before `save` replaces it. The copy is encrypted by `cipher` when
+/// encryption is on, like the library itself.
+pub(crate) fn upgrade_file<S: FnOnce(&Library) -> Result<()>>(
+    path: &Path,
+    mut library: Value,
+    cipher: &Cipher,
+    save: S,
+) -> Result<(Library, MigrationReport)> {
+    let mut report = upgrade(&mut library)?;
+    let library: Library =
+        serde_json::from_value(library).map_err(PickpocketError::corrupt_library)?;
+
+    let backup = backup_path(path, report.from);
+    cipher.copy(path, &backup)?;
+    save(&library)?;
+
+    report.backup = Some(backup);
+    Ok((library, report))
+}
+
+/// Where the library file at `path` is copied before upgrading it from `version`.
+pub fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.v{}.backup", name, version))
+}
+
+/// Unversioned libraries only kept the id, URL and title of articles. Sets
+/// the domain of each article to the host of its URL, so `--domain` filters
+/// and domain diversity see them without waiting for a renew.
+fn add_domains(library: &mut Value) -> Vec<String> {
+    let mut added = 0;
+
+    for inventory in &["read", "unread"] {
+        let articles = match library
+            .pointer_mut(&format!("/{}/articles", inventory))
+            .and_then(Value::as_object_mut)
+        {
+            Some(articles) => articles,
+            None => continue,
+        };
+
+        for article in articles.values_mut().filter_map(Value::as_object_mut) {
+            if article.contains_key("domain") {
+                continue;
+            }
+            let host = match article
+                .get("url")
+                .and_then(Value::as_str)
+                .and_then(Article::host)
+            {
+                Some(host) => host,
+                None => continue,
+            };
+
+            article.insert("domain".to_owned(), host.into());
+            added += 1;
+        }
+    }
+
+    if added == 0 {
+        return Vec::new();
+    }
+    vec![format!(
+        "Set the domain of {} articles to their site",
+        added
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::migration::{upgrade, version, SCHEMA_VERSION};
+    use serde_json::json;
+
+    #[test]
+    fn upgrades_unversioned_libraries_and_refuses_newer_ones() {
+        let mut library = json!({
+            "read": {"articles": {}},
+            "unread": {"articles": {
+                "1": {"id": "1", "url": "https://www.example.com/a", "title": "A"},
+                "2": {"id": "2", "url": "not a url", "title": "B"},
+            }},
+        });
+
+        let report = upgrade(&mut library).unwrap();
+        assert_eq!((1, SCHEMA_VERSION), (report.from, report.to));
+        assert_eq!(2, report.changes.len());
+        assert_eq!(SCHEMA_VERSION, version(&library));
+        let unread = &library["unread"]["articles"];
+        assert_eq!(json!("example.com"), unread["1"]["domain"]);
+        assert_eq!(None, unread["2"].get("domain"));
+
+        let report = upgrade(&mut library).unwrap();
+        assert!(!report.is_needed());
+        assert!(report.changes.is_empty());
+
+        let mut newer = json!({"schema_version": SCHEMA_VERSION + 1});
+        assert!(upgrade(&mut newer).is_err());
+    }
+}
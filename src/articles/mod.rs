@@ -1,4 +1,14 @@
 pub mod api;
 pub mod article;
+pub mod filter;
+pub mod history;
 pub mod inventory;
 pub mod library;
+pub mod migration;
+pub mod outbox;
+pub mod response;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod strategy;
@@ -0,0 +1,309 @@
+use crate::articles::api::API;
+use crate::configuration::Configuration;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use crate::files::write_atomically;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A change made locally, waiting to be sent to Pocket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Archive {
+        item_id: String,
+    },
+    Favorite {
+        item_id: String,
+    },
+    TagsAdd {
+        item_id: String,
+        tags: Vec<String>,
+    },
+    Delete {
+        item_id: String,
+    },
+    Add {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+}
+
+/// An action on the outbox, with its delivery attempts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub action: Action,
+    /// When the action happened locally, as a Unix timestamp.
+    pub time: i64,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Durable journal of actions waiting to be sent to Pocket, plus the ones
+/// Pocket kept rejecting.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Outbox {
+    #[serde(default)]
+    pending: Vec<Entry>,
+    #[serde(default)]
+    failed: Vec<Entry>,
+}
+
+/// Outcome of an [`Outbox::flush`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlushSummary {
+    /// Actions Pocket applied, now gone from the outbox.
+    pub sent: Vec<Action>,
+    /// Actions that were rejected too many times, now kept as failed.
+    pub failed: Vec<Action>,
+    /// Actions left for the next flush.
+    pub pending: usize,
+}
+
+impl Action {
+    /// The Pocket item this action is about (`None` for additions).
+    pub fn item_id(&self) -> Option<&str> {
+        match self {
+            Action::Archive { item_id }
+            | Action::Favorite { item_id }
+            | Action::TagsAdd { item_id, .. }
+            | Action::Delete { item_id } => Some(item_id),
+            Action::Add { .. } => None,
+        }
+    }
+
+    /// The action as expected by `/v3/send`.
+    pub fn to_pocket(&self, time: i64) -> Value {
+        let mut action = match self {
+            Action::Archive { item_id } => json!({ "action": "archive", "item_id": item_id }),
+            Action::Favorite { item_id } => json!({ "action": "favorite", "item_id": item_id }),
+            Action::TagsAdd { item_id, tags } => {
+                json!({ "action": "tags_add", "item_id": item_id, "tags": tags.join(",") })
+            }
+            Action::Delete { item_id } => json!({ "action": "delete", "item_id": item_id }),
+            Action::Add { url, title } => {
+                let mut action = json!({ "action": "add", "url": url });
+                if let Some(title) = title {
+                    action["title"] = json!(title);
+                }
+                action
+            }
+        };
+
+        action["time"] = json!(time.to_string());
+        action
+    }
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn pending(&self) -> &[Entry] {
+        &self.pending
+    }
+
+    pub fn failed(&self) -> &[Entry] {
+        &self.failed
+    }
+
+    /// Loads the outbox from disk, returning an empty one if it does not exist.
+    pub fn load(config: &Configuration) -> Result<Outbox> {
+        if !Path::new(&config.outbox_file).exists() {
+            return Ok(Outbox::new());
+        }
+
+        let cipher = Cipher::new(config);
+        let content = cipher.read_to_string(&config.outbox_file)?;
+        let outbox = serde_yaml::from_str::<Outbox>(&content)?;
+        if cipher.needs_sealing(&config.outbox_file) {
+            outbox.save(config)?;
+        }
+        Ok(outbox)
+    }
+
+    pub fn save(&self, config: &Configuration) -> Result<()> {
+        let outbox_string = serde_yaml::to_string(self)?;
+        let sealed = Cipher::new(config).seal(outbox_string.as_bytes())?;
+
+        write_atomically(&config.outbox_file, sealed)?;
+        Ok(())
+    }
+
+    /// Queues an action, unless the very same action is already waiting.
+    pub fn push(&mut self, action: Action) {
+        if self.pending.iter().any(|entry| entry.action == action) {
+            return;
+        }
+
+        self.pending.push(Entry {
+            action,
+            time: now(),
+            attempts: 0,
+            last_error: None,
+        });
+    }
+
+    /// Whether `action` is waiting to be sent, or was given up on.
+    pub fn contains(&self, action: &Action) -> bool {
+        self.pending
+            .iter()
+            .chain(self.failed.iter())
+            .any(|entry| &entry.action == action)
+    }
+
+    /// Drops `action` from the outbox, pending or failed. Returns whether it was there.
+    pub fn cancel(&mut self, action: &Action) -> bool {
+        let before = self.pending.len() + self.failed.len();
+
+        self.pending.retain(|entry| &entry.action != action);
+        self.failed.retain(|entry| &entry.action != action);
+
+        self.pending.len() + self.failed.len() < before
+    }
+
+    /// Moves every failed action back to the pending queue, with fresh attempts.
+    pub fn retry_failed(&mut self) -> usize {
+        let count = self.failed.len();
+
+        for mut entry in self.failed.drain(..) {
+            entry.attempts = 0;
+            self.pending.push(entry);
+        }
+
+        count
+    }
+
+    pub fn clear_failed(&mut self) -> usize {
+        let count = self.failed.len();
+        self.failed.clear();
+        count
+    }
+
+    /// Sends every pending action to Pocket. Applied actions leave the outbox;
+    /// rejected ones are retried on the next flush, until they reach
+    /// `max_attempts` and are kept as failed.
+    ///
+    /// When Pocket rejects the whole batch as invalid, actions are sent one by
+    /// one, so a single bad action does not hold back the others. Any other
+    /// error (connection, authorization, Pocket being down) leaves the outbox
+    /// untouched.
+    pub fn flush(&mut self, api: &API, max_attempts: u32) -> Result<FlushSummary> {
+        // Create a runtime for async operations
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(self.flush_async(api, max_attempts))
+    }
+
+    /// Async version of [`Outbox::flush`].
+    pub async fn flush_async(&mut self, api: &API, max_attempts: u32) -> Result<FlushSummary> {
+        let actions: Vec<Value> = self
+            .pending
+            .iter()
+            .map(|entry| entry.action.to_pocket(entry.time))
+            .collect();
+
+        let results: Vec<std::result::Result<(), String>> = match api.send_async(&actions).await {
+            Ok(results) => results
+                .into_iter()
+                .map(|applied| match applied {
+                    true => Ok(()),
+                    false => Err("Pocket did not apply the action".to_owned()),
+                })
+                .collect(),
+            Err(PickpocketError::Pocket { status: 400, .. }) => {
+                let mut results = Vec::new();
+                for action in actions {
+                    results.push(match api.send_async(&[action]).await {
+                        Ok(applied) if applied == [true] => Ok(()),
+                        Ok(_) => Err("Pocket did not apply the action".to_owned()),
+                        Err(error @ PickpocketError::Pocket { status: 400, .. }) => {
+                            Err(error.to_string())
+                        }
+                        Err(error) => return Err(error),
+                    });
+                }
+                results
+            }
+            Err(error) => return Err(error),
+        };
+
+        let mut summary = FlushSummary::default();
+
+        for (mut entry, result) in self
+            .pending
+            .drain(..)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .zip(results)
+        {
+            match result {
+                Ok(()) => summary.sent.push(entry.action),
+                Err(error) => {
+                    entry.attempts += 1;
+                    entry.last_error = Some(error);
+
+                    if entry.attempts >= max_attempts {
+                        summary.failed.push(entry.action.clone());
+                        self.failed.push(entry);
+                    } else {
+                        self.pending.push(entry);
+                    }
+                }
+            }
+        }
+
+        summary.pending = self.pending.len();
+        Ok(summary)
+    }
+}
+
+/// The current Unix timestamp.
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::outbox::{Action, Outbox};
+
+    #[test]
+    fn converts_actions_to_pocket_format() {
+        let action = Action::TagsAdd {
+            item_id: "1".to_owned(),
+            tags: vec!["rust".to_owned(), "cli".to_owned()],
+        };
+        let pocket = action.to_pocket(1600000000);
+
+        assert_eq!("tags_add", pocket["action"]);
+        assert_eq!("rust,cli", pocket["tags"]);
+        assert_eq!("1600000000", pocket["time"]);
+    }
+
+    #[test]
+    fn queues_each_action_once() {
+        let mut outbox = Outbox::new();
+        let archive = Action::Archive {
+            item_id: "1".to_owned(),
+        };
+
+        outbox.push(archive.clone());
+        outbox.push(archive.clone());
+
+        assert_eq!(1, outbox.pending().len());
+        assert!(outbox.contains(&archive));
+
+        let yaml = serde_yaml::to_string(&outbox).unwrap();
+        let loaded: Outbox = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(outbox.pending(), loaded.pending());
+    }
+}
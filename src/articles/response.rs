@@ -0,0 +1,302 @@
+//! Models for Pocket's `/v3/get` and `/v3/send` responses.
+//!
+//! Pocket is loose with its JSON: numbers and flags usually come as strings,
+//! empty collections come as `[]` instead of `{}`, and most fields are missing
+//! when they don't apply. These models accept all of that.
+
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Response of `/v3/get`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RetrieveResponse {
+    #[serde(default, deserialize_with = "number")]
+    pub status: Option<i64>,
+    #[serde(default, deserialize_with = "map")]
+    pub list: HashMap<String, Item>,
+    #[serde(default, deserialize_with = "number")]
+    pub since: Option<i64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Response of `/v3/send`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SendResponse {
+    #[serde(default, deserialize_with = "number")]
+    pub status: Option<i64>,
+    /// Whether each action was applied, in the order they were sent.
+    #[serde(default, deserialize_with = "results")]
+    pub action_results: Vec<bool>,
+}
+
+/// Status of an item on Pocket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemStatus {
+    #[default]
+    Unread,
+    Archived,
+    Deleted,
+}
+
+/// A single item of a `/v3/get` response.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Item {
+    #[serde(default)]
+    pub item_id: String,
+    #[serde(default)]
+    pub given_url: Option<String>,
+    #[serde(default)]
+    pub resolved_url: Option<String>,
+    #[serde(default)]
+    pub given_title: Option<String>,
+    #[serde(default)]
+    pub resolved_title: Option<String>,
+    #[serde(default)]
+    pub excerpt: Option<String>,
+    #[serde(default, deserialize_with = "number")]
+    pub word_count: Option<i64>,
+    #[serde(default, deserialize_with = "number")]
+    pub time_added: Option<i64>,
+    #[serde(default, deserialize_with = "flag")]
+    pub favorite: bool,
+    #[serde(default)]
+    pub status: ItemStatus,
+    #[serde(default, deserialize_with = "map")]
+    pub tags: HashMap<String, Tag>,
+    #[serde(default, deserialize_with = "map")]
+    pub authors: HashMap<String, Author>,
+    #[serde(default, deserialize_with = "map")]
+    pub images: HashMap<String, Image>,
+    #[serde(default)]
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub domain_metadata: Option<DomainMetadata>,
+    #[serde(default, deserialize_with = "number")]
+    pub time_to_read: Option<i64>,
+    #[serde(default, deserialize_with = "flag")]
+    pub is_article: bool,
+    #[serde(default, deserialize_with = "flag")]
+    pub has_video: bool,
+    #[serde(default, deserialize_with = "flag")]
+    pub has_image: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Tag {
+    #[serde(default)]
+    pub tag: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Author {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DomainMetadata {
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Image {
+    #[serde(default)]
+    pub src: String,
+    #[serde(default, deserialize_with = "number")]
+    pub width: Option<i64>,
+    #[serde(default, deserialize_with = "number")]
+    pub height: Option<i64>,
+    #[serde(default)]
+    pub caption: Option<String>,
+}
+
+impl Item {
+    /// The URL the user saved, falling back to the one Pocket resolved.
+    pub fn url(&self) -> Option<&str> {
+        non_empty(&self.given_url).or_else(|| non_empty(&self.resolved_url))
+    }
+
+    /// The resolved title, falling back to the title the user saved.
+    pub fn title(&self) -> &str {
+        non_empty(&self.resolved_title)
+            .or_else(|| non_empty(&self.given_title))
+            .unwrap_or("")
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+impl<'de> Deserialize<'de> for ItemStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match number(deserializer)? {
+            Some(1) => Ok(ItemStatus::Archived),
+            Some(2) => Ok(ItemStatus::Deleted),
+            _ => Ok(ItemStatus::Unread),
+        }
+    }
+}
+
+/// A number, given either as a JSON number or a string ("", null and missing are `None`).
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Loose {
+        Integer(i64),
+        Float(f64),
+        Text(String),
+        Bool(bool),
+    }
+
+    Ok(match Option::<Loose>::deserialize(deserializer)? {
+        Some(Loose::Integer(number)) => Some(number),
+        Some(Loose::Float(number)) => Some(number as i64),
+        Some(Loose::Text(text)) => text.trim().parse().ok(),
+        Some(Loose::Bool(flag)) => Some(flag as i64),
+        None => None,
+    })
+}
+
+/// Action results: `false` (or `null`) for failed actions, `true` or an item
+/// object for applied ones.
+fn results<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+    let results = Option::<Vec<serde_json::Value>>::deserialize(deserializer)?;
+
+    Ok(results
+        .unwrap_or_default()
+        .iter()
+        .map(|result| {
+            !matches!(
+                result,
+                serde_json::Value::Bool(false) | serde_json::Value::Null
+            )
+        })
+        .collect())
+}
+
+/// A flag, given as `"0"`/`"1"`, a number or a boolean.
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(number(deserializer)?.unwrap_or(0) != 0)
+}
+
+/// A map that Pocket may send as `[]` (or `null`) when empty.
+fn map<'de, D, T>(deserializer: D) -> Result<HashMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct MapVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for MapVisitor<T> {
+        type Value = HashMap<String, T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an object or an empty array")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut map = HashMap::new();
+            while let Some((key, value)) = access.next_entry()? {
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            match access.next_element::<de::IgnoredAny>()? {
+                None => Ok(HashMap::new()),
+                Some(_) => Err(de::Error::invalid_length(1, &self)),
+            }
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(HashMap::new())
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(HashMap::new())
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    deserializer.deserialize_option(MapVisitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::response::{ItemStatus, RetrieveResponse, SendResponse};
+
+    #[test]
+    fn parses_items_with_pocket_quirks() {
+        let response: RetrieveResponse = serde_json::from_str(
+            r#"{
+                "status": 1,
+                "since": "1700000000",
+                "error": null,
+                "list": {
+                    "42": {
+                        "item_id": "42",
+                        "given_url": "",
+                        "resolved_url": "https://example.com/resolved",
+                        "given_title": "Given",
+                        "resolved_title": "",
+                        "word_count": "",
+                        "time_added": "1600000000",
+                        "favorite": "1",
+                        "status": "1",
+                        "tags": {"rust": {"item_id": "42", "tag": "rust"}},
+                        "authors": [],
+                        "images": {"1": {"src": "https://example.com/a.png", "width": "0"}}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let item = &response.list["42"];
+        assert_eq!(Some(1700000000), response.since);
+        assert_eq!(Some("https://example.com/resolved"), item.url());
+        assert_eq!("Given", item.title());
+        assert_eq!(None, item.word_count);
+        assert_eq!(Some(1600000000), item.time_added);
+        assert!(item.favorite);
+        assert_eq!(ItemStatus::Archived, item.status);
+        assert_eq!("rust", item.tags["rust"].tag);
+        assert!(item.authors.is_empty());
+        assert_eq!(Some(0), item.images["1"].width);
+    }
+
+    #[test]
+    fn accepts_an_empty_array_as_list() {
+        let response: RetrieveResponse =
+            serde_json::from_str(r#"{"status": 2, "complete": 1, "list": [], "error": null}"#)
+                .unwrap();
+
+        assert!(response.list.is_empty());
+        assert!(serde_json::from_str::<RetrieveResponse>(r#"{"list": [1]}"#).is_err());
+    }
+
+    #[test]
+    fn parses_action_results() {
+        let response: SendResponse = serde_json::from_str(
+            r#"{"status": 1, "action_results": [true, false, {"item_id": "1"}, null]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec![true, false, true, false], response.action_results);
+    }
+}
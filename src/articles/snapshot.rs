@@ -0,0 +1,156 @@
+use crate::articles::library::Library;
+use crate::articles::migration;
+use crate::articles::outbox::now;
+use crate::configuration::Configuration;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use crate::files::write_atomically;
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+static INFO_FILE_NAME: &str = "snapshot";
+static LIBRARY_FILE_NAME: &str = "library";
+static OUTBOX_FILE_NAME: &str = "outbox";
+
+/// A copy of the local library and outbox, taken before an operation that
+/// could lose local changes. Kept on its own folder of `snapshots_folder`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    #[serde(skip)]
+    pub id: String,
+    /// When the snapshot was taken, as a Unix timestamp.
+    pub time: i64,
+    /// The operation the snapshot was taken before, e.g. `renew`.
+    pub operation: String,
+}
+
+impl Snapshot {
+    /// Copies `library` and the outbox aside before `operation`, then drops
+    /// the oldest snapshots beyond `snapshot_retention`.
+    pub fn take(config: &Configuration, operation: &str, library: &Library) -> Result<Snapshot> {
+        let time = now();
+        let stamp = Local
+            .timestamp_opt(time, 0)
+            .single()
+            .map(|time| time.format("%Y%m%d-%H%M%S").to_string())
+            .unwrap_or_else(|| time.to_string());
+
+        let mut id = stamp.clone();
+        let mut attempt = 1;
+        while config.snapshots_folder.join(&id).exists() {
+            attempt += 1;
+            id = format!("{}-{}", stamp, attempt);
+        }
+
+        let folder = config.snapshots_folder.join(&id);
+        std::fs::create_dir_all(&folder)?;
+
+        let snapshot = Snapshot {
+            id,
+            time,
+            operation: operation.to_owned(),
+        };
+        let cipher = Cipher::new(config);
+        let library = serde_yaml::to_string(library)?;
+        write_atomically(
+            &folder.join(LIBRARY_FILE_NAME),
+            cipher.seal(library.as_bytes())?,
+        )?;
+        if config.outbox_file.exists() {
+            cipher.copy(&config.outbox_file, &folder.join(OUTBOX_FILE_NAME))?;
+        }
+        // Written last: folders without it are incomplete and ignored
+        write_atomically(
+            &folder.join(INFO_FILE_NAME),
+            serde_yaml::to_string(&snapshot)?,
+        )?;
+
+        Snapshot::prune(config)?;
+        Snapshot::seal(config, &cipher)?;
+        Ok(snapshot)
+    }
+
+    /// Every snapshot, oldest first.
+    pub fn list(config: &Configuration) -> Result<Vec<Snapshot>> {
+        if !config.snapshots_folder.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in std::fs::read_dir(&config.snapshots_folder)? {
+            let entry = entry?;
+            let info = entry.path().join(INFO_FILE_NAME);
+            if !info.exists() {
+                continue;
+            }
+
+            let mut snapshot: Snapshot = serde_yaml::from_str(&std::fs::read_to_string(info)?)?;
+            snapshot.id = entry.file_name().to_string_lossy().into_owned();
+            snapshots.push(snapshot);
+        }
+
+        snapshots.sort_by(|a, b| (a.time, &a.id).cmp(&(b.time, &b.id)));
+        Ok(snapshots)
+    }
+
+    /// Puts back the library and outbox of snapshot `id`. The current ones are
+    /// snapshotted first, so a restore can be undone as well.
+    pub fn restore(config: &Configuration, id: &str) -> Result<Snapshot> {
+        let snapshot = Snapshot::list(config)?
+            .into_iter()
+            .find(|snapshot| snapshot.id == id)
+            .ok_or_else(|| {
+                PickpocketError::InvalidArgument(format!("Snapshot {} does not exist", id))
+            })?;
+        let folder = snapshot.folder(config);
+
+        // Snapshots taken by older releases are upgraded like library files
+        let content = Cipher::new(config).read_to_string(&folder.join(LIBRARY_FILE_NAME))?;
+        let mut raw = serde_yaml::from_str(&content).map_err(PickpocketError::corrupt_library)?;
+        migration::upgrade(&mut raw)?;
+        let library: Library =
+            serde_json::from_value(raw).map_err(PickpocketError::corrupt_library)?;
+
+        let store = config.storage.open(config)?;
+        Snapshot::take(config, "restore", &store.load()?)?;
+        store.save(&library)?;
+
+        let outbox = folder.join(OUTBOX_FILE_NAME);
+        if outbox.exists() {
+            write_atomically(&config.outbox_file, std::fs::read(outbox)?)?;
+        } else if config.outbox_file.exists() {
+            std::fs::remove_file(&config.outbox_file)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    fn folder(&self, config: &Configuration) -> PathBuf {
+        config.snapshots_folder.join(&self.id)
+    }
+
+    /// Encrypts the snapshots taken before encryption was turned on.
+    fn seal(config: &Configuration, cipher: &Cipher) -> Result<()> {
+        if !cipher.encrypts() {
+            return Ok(());
+        }
+
+        for snapshot in Snapshot::list(config)? {
+            let folder = snapshot.folder(config);
+            cipher.seal_file(&folder.join(LIBRARY_FILE_NAME))?;
+            cipher.seal_file(&folder.join(OUTBOX_FILE_NAME))?;
+        }
+        Ok(())
+    }
+
+    fn prune(config: &Configuration) -> Result<()> {
+        let snapshots = Snapshot::list(config)?;
+        let excess = snapshots.len().saturating_sub(config.snapshot_retention);
+
+        for snapshot in &snapshots[..excess] {
+            std::fs::remove_dir_all(snapshot.folder(config))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,720 @@
+use crate::articles::article::Article;
+use crate::articles::history::{History, Pick};
+use crate::articles::inventory::Inventory;
+use crate::articles::library::Library;
+use crate::articles::store::{LibraryStore, YamlStore};
+use crate::configuration::Configuration;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction, TransactionBehavior};
+use std::collections::HashMap;
+
+static SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS articles (
+        id TEXT PRIMARY KEY NOT NULL,
+        state TEXT NOT NULL,
+        url TEXT NOT NULL,
+        title TEXT NOT NULL,
+        resolved_url TEXT,
+        excerpt TEXT,
+        word_count INTEGER,
+        reading_time INTEGER,
+        time_added INTEGER,
+        favorite INTEGER NOT NULL DEFAULT 0,
+        authors TEXT NOT NULL DEFAULT '[]',
+        domain TEXT,
+        language TEXT,
+        is_article INTEGER NOT NULL DEFAULT 0,
+        has_video INTEGER NOT NULL DEFAULT 0,
+        has_image INTEGER NOT NULL DEFAULT 0,
+        snoozed_until INTEGER,
+        bag_position INTEGER,
+        publisher TEXT
+    );
+    CREATE INDEX IF NOT EXISTS articles_state ON articles (state);
+    CREATE INDEX IF NOT EXISTS articles_time_added ON articles (time_added);
+    CREATE INDEX IF NOT EXISTS articles_snoozed_until ON articles (snoozed_until);
+
+    CREATE TABLE IF NOT EXISTS tags (
+        article_id TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (article_id, tag)
+    );
+    CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+
+    CREATE TABLE IF NOT EXISTS history (
+        position INTEGER PRIMARY KEY,
+        time INTEGER NOT NULL,
+        opening TEXT NOT NULL,
+        article_id TEXT NOT NULL,
+        article TEXT NOT NULL,
+        undone INTEGER,
+        archived INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS history_time ON history (time);
+    CREATE INDEX IF NOT EXISTS history_article_id ON history (article_id);
+
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY NOT NULL,
+        value TEXT NOT NULL
+    );
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS articles_search USING fts5 (
+        id UNINDEXED,
+        title,
+        excerpt
+    );
+";
+
+/// Room left between shuffle bag positions, for articles joining the bag.
+static BAG_GAP: i64 = 1 << 20;
+
+static ARTICLE_COLUMNS: &str = "id, state, url, title, resolved_url, excerpt, word_count, \
+    reading_time, time_added, favorite, authors, domain, language, is_article, has_video, \
+    has_image, snoozed_until, bag_position, publisher";
+
+/// The library as a SQLite database at `database_file`, with the history
+/// alongside it. Titles and excerpts are indexed for [`SqliteStore::search`].
+///
+/// The first time the database is opened, it imports the existing YAML
+/// `library_file` and `history_file`, which are left in place. The import is
+/// recorded on `settings` once it succeeds, so a failed one runs again.
+///
+/// Saves and updates only write the articles that changed.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(config: &Configuration) -> Result<SqliteStore> {
+        if config.encryption {
+            return Err(PickpocketError::InvalidConfiguration(
+                "encryption is not available with the sqlite storage, as the database is not encrypted"
+                    .to_owned(),
+            ));
+        }
+
+        let connection = Connection::open(&config.database_file)?;
+        let store = SqliteStore { connection };
+
+        let transaction =
+            Transaction::new_unchecked(&store.connection, TransactionBehavior::Immediate)?;
+        transaction.execute_batch(SCHEMA)?;
+        if !SqliteStore::imported(&transaction)? {
+            SqliteStore::import(&transaction, config)?;
+        }
+        transaction.commit()?;
+
+        Ok(store)
+    }
+
+    /// Whether the YAML files were imported already.
+    fn imported(transaction: &Transaction) -> Result<bool> {
+        Ok(transaction.query_row(
+            "SELECT EXISTS (SELECT 1 FROM settings WHERE key = 'imported')",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Copies the YAML library and the history journal into the database.
+    fn import(transaction: &Transaction, config: &Configuration) -> Result<()> {
+        if config.library_file.exists() {
+            let library = YamlStore {
+                path: config.library_file.clone(),
+                cipher: Cipher::new(config),
+            }
+            .load()?;
+            SqliteStore::write(transaction, &Library::new(), &library)?;
+        }
+
+        if config.history_file.exists() {
+            let history = History::load_file(&config.history_file, &Cipher::new(config))?;
+            transaction.execute("DELETE FROM history", [])?;
+            SqliteStore::insert_picks(transaction, history.picks())?;
+        }
+
+        transaction.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('imported', '1')",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Articles whose title or excerpt match the FTS5 `query`, best matches
+    /// first, along with whether they are `unread`, `read` or `snoozed`.
+    pub fn search(&self, query: &str) -> Result<Vec<(String, Article)>> {
+        let mut statement = self.connection.prepare(&format!(
+            "WITH matches AS (
+                 SELECT id AS match_id, rank FROM articles_search WHERE articles_search MATCH ?1
+             )
+             SELECT {} FROM matches JOIN articles ON id = match_id ORDER BY rank",
+            ARTICLE_COLUMNS
+        ))?;
+        let mut found = statement
+            .query_map(params![query], SqliteStore::article)?
+            .map(|row| row.map(|(state, article, _)| (state, article)))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut tags = self
+            .connection
+            .prepare("SELECT tag FROM tags WHERE article_id = ?1 ORDER BY tag")?;
+        for (_, article) in found.iter_mut() {
+            article.tags = tags
+                .query_map(params![article.id], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+        }
+
+        Ok(found)
+    }
+
+    pub fn load_history(&self) -> Result<History> {
+        let mut statement = self.connection.prepare(
+            "SELECT time, opening, article, undone, archived FROM history ORDER BY position",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut picks = Vec::with_capacity(rows.len());
+        for (time, opening, article, undone, archived) in rows {
+            picks.push(Pick {
+                time,
+                opening: serde_json::from_value(serde_json::Value::String(opening))?,
+                article: serde_json::from_str(&article)?,
+                undone,
+                archived,
+            });
+        }
+
+        Ok(History::from_picks(picks))
+    }
+
+    pub fn save_history(&self, history: &History) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute("DELETE FROM history", [])?;
+        SqliteStore::insert_picks(&transaction, history.picks())?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn record(&self, picks: &[Pick]) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        SqliteStore::insert_picks(&transaction, picks)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn insert_picks(transaction: &Transaction, picks: &[Pick]) -> Result<()> {
+        let mut insert = transaction.prepare(
+            "INSERT INTO history (time, opening, article_id, article, undone, archived)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for pick in picks {
+            let opening = match serde_json::to_value(pick.opening)? {
+                serde_json::Value::String(opening) => opening,
+                _ => unreachable!("openings serialize to strings"),
+            };
+            insert.execute(params![
+                pick.time,
+                opening,
+                pick.article.id,
+                serde_json::to_string(&pick.article)?,
+                pick.undone,
+                pick.archived,
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Library> {
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        let mut statement = self
+            .connection
+            .prepare("SELECT article_id, tag FROM tags ORDER BY article_id, tag")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            tags.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+
+        let mut inventories: HashMap<String, Inventory> = HashMap::new();
+        let mut bag = Vec::new();
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT {} FROM articles", ARTICLE_COLUMNS))?;
+        let articles = statement.query_map([], SqliteStore::article)?;
+        for row in articles {
+            let (state, mut article, bag_position) = row?;
+            article.tags = tags.remove(&article.id).unwrap_or_default();
+            if let Some(position) = bag_position {
+                bag.push((position, article.id.clone()));
+            }
+            inventories
+                .entry(state)
+                .or_default()
+                .articles
+                .insert(article.id.clone(), article);
+        }
+        bag.sort();
+
+        let since = self
+            .connection
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'since'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .and_then(|since| since.parse().ok());
+
+        let mut inventory = |state: &str| inventories.remove(state).unwrap_or_default();
+        Ok(Library::from_parts(
+            inventory("read"),
+            inventory("unread"),
+            inventory("snoozed"),
+            since,
+            bag.into_iter().map(|(_, id)| id).collect(),
+        ))
+    }
+
+    /// Brings the database from `previous`, the library it holds, to
+    /// `library`, writing only the articles that changed.
+    fn write(transaction: &Transaction, previous: &Library, library: &Library) -> Result<()> {
+        let before = SqliteStore::rows(previous);
+        let after = SqliteStore::rows(library);
+        let stored = SqliteStore::stored_bag_positions(transaction)?;
+        let bag = SqliteStore::bag_positions(&stored, library.bag());
+
+        let mut upsert = transaction.prepare(&format!(
+            "INSERT INTO articles ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+             ON CONFLICT (id) DO UPDATE SET state = ?2, url = ?3, title = ?4, resolved_url = ?5,
+                 excerpt = ?6, word_count = ?7, reading_time = ?8, time_added = ?9, favorite = ?10,
+                 authors = ?11, domain = ?12, language = ?13, is_article = ?14, has_video = ?15,
+                 has_image = ?16, snoozed_until = ?17, bag_position = ?18, publisher = ?19",
+            ARTICLE_COLUMNS
+        ))?;
+        let mut delete = transaction.prepare("DELETE FROM articles WHERE id = ?1")?;
+        let mut delete_tags = transaction.prepare("DELETE FROM tags WHERE article_id = ?1")?;
+        let mut insert_tag =
+            transaction.prepare("INSERT OR IGNORE INTO tags (article_id, tag) VALUES (?1, ?2)")?;
+        // Search rows share the rowid of their article, which updates keep
+        let mut delete_search = transaction.prepare(
+            "DELETE FROM articles_search WHERE rowid = (SELECT rowid FROM articles WHERE id = ?1)",
+        )?;
+        let mut insert_search = transaction.prepare(
+            "INSERT INTO articles_search (rowid, id, title, excerpt)
+             SELECT rowid, id, title, excerpt FROM articles WHERE id = ?1",
+        )?;
+
+        for (id, (state, article)) in &after {
+            let position = bag.get(*id).copied();
+            let previous = before.get(id);
+            if let Some((previous_state, previous_article)) = previous {
+                if previous_state == state
+                    && previous_article == article
+                    && stored.get(*id).copied() == position
+                {
+                    continue;
+                }
+            }
+
+            let indexed = previous.is_some_and(|(_, previous)| {
+                previous.title == article.title && previous.excerpt == article.excerpt
+            });
+            if previous.is_some() && !indexed {
+                delete_search.execute(params![id])?;
+            }
+            upsert.execute(params![
+                article.id,
+                state,
+                article.url,
+                article.title,
+                article.resolved_url,
+                article.excerpt,
+                article.word_count.map(|count| count as i64),
+                article.reading_time.map(|minutes| minutes as i64),
+                article.time_added,
+                article.favorite,
+                serde_json::to_string(&article.authors)?,
+                article.domain,
+                article.language,
+                article.is_article,
+                article.has_video,
+                article.has_image,
+                article.snoozed_until,
+                position,
+                article.publisher,
+            ])?;
+            if !indexed {
+                insert_search.execute(params![id])?;
+            }
+
+            if previous.map(|(_, previous)| &previous.tags) != Some(&article.tags) {
+                delete_tags.execute(params![id])?;
+                for tag in &article.tags {
+                    insert_tag.execute(params![id, tag])?;
+                }
+            }
+        }
+
+        for id in before.keys().filter(|id| !after.contains_key(*id)) {
+            delete_search.execute(params![id])?;
+            delete_tags.execute(params![id])?;
+            delete.execute(params![id])?;
+        }
+
+        if previous.since() != library.since() {
+            transaction.execute("DELETE FROM settings WHERE key = 'since'", [])?;
+            if let Some(since) = library.since() {
+                transaction.execute(
+                    "INSERT INTO settings (key, value) VALUES ('since', ?1)",
+                    params![since.to_string()],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every article of `library` by id, along with its state.
+    fn rows(library: &Library) -> HashMap<&str, (&'static str, &Article)> {
+        [
+            ("unread", library.unread()),
+            ("read", library.read()),
+            ("snoozed", library.snoozed()),
+        ]
+        .iter()
+        .flat_map(|&(state, inventory)| {
+            inventory
+                .articles
+                .values()
+                .map(move |article| (article.id.as_str(), (state, article)))
+        })
+        .collect()
+    }
+
+    /// Positions for `bag`, sparse so that articles joining the bag fit
+    /// between the stored ones. Stored positions are kept while they stay in
+    /// order; when there's no room left, the whole bag is numbered again.
+    fn bag_positions<'a>(
+        stored: &HashMap<String, i64>,
+        bag: &'a [String],
+    ) -> HashMap<&'a str, i64> {
+        let mut last = i64::MIN;
+        let kept: Vec<Option<i64>> = bag
+            .iter()
+            .map(|id| match stored.get(id) {
+                Some(&position) if position > last => {
+                    last = position;
+                    Some(position)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let renumbered = || {
+            bag.iter()
+                .enumerate()
+                .map(|(index, id)| (id.as_str(), (index as i64 + 1) * BAG_GAP))
+                .collect()
+        };
+
+        let mut positions = HashMap::with_capacity(bag.len());
+        let (mut index, mut low) = (0, 0);
+        while index < bag.len() {
+            if let Some(position) = kept[index] {
+                positions.insert(bag[index].as_str(), position);
+                low = position;
+                index += 1;
+                continue;
+            }
+
+            let start = index;
+            while index < bag.len() && kept[index].is_none() {
+                index += 1;
+            }
+            let run = (index - start) as i64;
+            let high = kept
+                .get(index)
+                .copied()
+                .flatten()
+                .unwrap_or(low + BAG_GAP * (run + 1));
+            let step = (high - low) / (run + 1);
+            if step == 0 {
+                return renumbered();
+            }
+            for (offset, id) in bag[start..index].iter().enumerate() {
+                positions.insert(id.as_str(), low + step * (offset as i64 + 1));
+            }
+        }
+
+        positions
+    }
+
+    fn stored_bag_positions(transaction: &Transaction) -> Result<HashMap<String, i64>> {
+        let mut stored = HashMap::new();
+        let mut statement = transaction
+            .prepare("SELECT id, bag_position FROM articles WHERE bag_position IS NOT NULL")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            stored.insert(row.get(0)?, row.get(1)?);
+        }
+        Ok(stored)
+    }
+
+    /// Reads a row selected with [`ARTICLE_COLUMNS`]: its state, the article
+    /// (tags aside) and its shuffle bag position.
+    fn article(row: &Row) -> rusqlite::Result<(String, Article, Option<i64>)> {
+        let authors: String = row.get(10)?;
+
+        let article = Article {
+            id: row.get(0)?,
+            url: row.get(2)?,
+            title: row.get(3)?,
+            resolved_url: row.get(4)?,
+            excerpt: row.get(5)?,
+            word_count: row.get::<_, Option<i64>>(6)?.map(|count| count as u64),
+            reading_time: row.get::<_, Option<i64>>(7)?.map(|minutes| minutes as u64),
+            time_added: row.get(8)?,
+            favorite: row.get(9)?,
+            tags: Vec::new(),
+            authors: serde_json::from_str(&authors).unwrap_or_default(),
+            domain: row.get(11)?,
+            publisher: row.get(18)?,
+            language: row.get(12)?,
+            is_article: row.get(13)?,
+            has_video: row.get(14)?,
+            has_image: row.get(15)?,
+            snoozed_until: row.get(16)?,
+        };
+
+        Ok((row.get(1)?, article, row.get(17)?))
+    }
+}
+
+impl LibraryStore for SqliteStore {
+    fn load(&self) -> Result<Library> {
+        self.read()
+    }
+
+    fn save(&self, library: &Library) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        SqliteStore::write(&transaction, &self.read()?, library)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Runs in a single write transaction, so concurrent updates wait for
+    /// each other.
+    fn update(&self, change: &mut dyn FnMut(&mut Library) -> Result<()>) -> Result<()> {
+        let transaction =
+            Transaction::new_unchecked(&self.connection, TransactionBehavior::Immediate)?;
+        let previous = self.read()?;
+        let mut library = previous.clone();
+
+        change(&mut library)?;
+        SqliteStore::write(&transaction, &previous, &library)?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::article::Article;
+    use crate::articles::history::History;
+    use crate::articles::inventory::Inventory;
+    use crate::articles::library::Library;
+    use crate::articles::sqlite::SqliteStore;
+    use crate::articles::store::{LibraryStore, YamlStore};
+    use crate::configuration::Configuration;
+    use crate::error::PickpocketError;
+
+    #[test]
+    fn imports_the_yaml_library_and_searches_it() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Configuration {
+            library_file: home.path().join("library_file"),
+            history_file: home.path().join("history"),
+            database_file: home.path().join("library.sqlite3"),
+            ..Default::default()
+        };
+        let yaml = "
+read:
+  articles:
+    '1': {id: '1', url: 'https://example.com/1', title: 'Rust in production', tags: [rust]}
+unread:
+  articles:
+    '2': {id: '2', url: 'https://example.com/2', title: 'Gardening', excerpt: 'Growing rust resistant roses', favorite: true, authors: [Ann]}
+    '3': {id: '3', url: 'https://example.com/3', title: 'Cooking'}
+since: 42
+bag: ['3', '2']
+";
+        std::fs::write(&config.library_file, yaml).unwrap();
+        let history = "{\"time\":10,\"opening\":\"not_opened\",\"article\":{\"id\":\"1\",\"url\":\"https://example.com/1\",\"title\":\"Rust in production\"}}\n";
+        std::fs::write(&config.history_file, history).unwrap();
+
+        let store = SqliteStore::open(&config).unwrap();
+        let library = store.load().unwrap();
+        let expected = YamlStore {
+            path: config.library_file.clone(),
+            cipher: Default::default(),
+        }
+        .load()
+        .unwrap();
+
+        assert_eq!(expected.unread().articles, library.unread().articles);
+        assert_eq!(expected.read().articles, library.read().articles);
+        assert_eq!(Some(42), library.since());
+        assert_eq!(&["3".to_owned(), "2".to_owned()], library.bag());
+        assert_eq!(
+            History::load_file(&config.history_file, &Default::default())
+                .unwrap()
+                .picks(),
+            store.load_history().unwrap().picks()
+        );
+
+        let found: Vec<(String, String)> = store
+            .search("rust")
+            .unwrap()
+            .into_iter()
+            .map(|(state, article)| (state, article.id))
+            .collect();
+        assert_eq!(2, found.len());
+        assert!(found.contains(&("read".to_owned(), "1".to_owned())));
+        assert!(found.contains(&("unread".to_owned(), "2".to_owned())));
+
+        // Only imported once
+        store.save(&Library::new()).unwrap();
+        let reopened = SqliteStore::open(&config).unwrap();
+        assert!(reopened.load().unwrap().unread().is_empty());
+    }
+
+    #[test]
+    fn writes_only_the_articles_that_changed() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Configuration {
+            library_file: home.path().join("library_file"),
+            database_file: home.path().join("library.sqlite3"),
+            ..Default::default()
+        };
+        let store = SqliteStore::open(&config).unwrap();
+
+        let mut unread = Inventory::new();
+        for id in 1..=100 {
+            let article = Article {
+                id: id.to_string(),
+                url: format!("https://example.com/{}", id),
+                title: format!("Article {}", id),
+                tags: vec!["rust".to_owned()],
+                ..Default::default()
+            };
+            unread.articles.insert(article.id.clone(), article);
+        }
+        let bag: Vec<String> = (1..=100).map(|id| id.to_string()).collect();
+        store
+            .save(&Library::from_parts(
+                Inventory::new(),
+                unread,
+                Inventory::new(),
+                Some(1),
+                bag,
+            ))
+            .unwrap();
+
+        store
+            .connection
+            .execute_batch(
+                "CREATE TEMP TABLE writes (id TEXT);
+                 CREATE TEMP TRIGGER inserts AFTER INSERT ON articles
+                     BEGIN INSERT INTO writes VALUES (new.id); END;
+                 CREATE TEMP TRIGGER updates AFTER UPDATE ON articles
+                     BEGIN INSERT INTO writes VALUES (new.id); END;
+                 CREATE TEMP TRIGGER deletes AFTER DELETE ON articles
+                     BEGIN INSERT INTO writes VALUES (old.id); END;",
+            )
+            .unwrap();
+
+        // Reading the first article of the bag, and a new one joining it
+        store
+            .update(&mut |library: &mut Library| {
+                let mut read = library.read().clone();
+                let mut unread = library.unread().clone();
+                let mut bag = library.bag()[1..].to_vec();
+                read.articles
+                    .insert("1".to_owned(), unread.articles.remove("1").unwrap());
+                let article = Article {
+                    id: "101".to_owned(),
+                    url: "https://example.com/101".to_owned(),
+                    title: "Article 101".to_owned(),
+                    ..Default::default()
+                };
+                unread.articles.insert(article.id.clone(), article);
+                bag.insert(50, "101".to_owned());
+
+                *library = Library::from_parts(read, unread, Inventory::new(), Some(1), bag);
+                Ok(())
+            })
+            .unwrap();
+
+        let written: Vec<String> = store
+            .connection
+            .prepare("SELECT id FROM writes ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(vec!["1", "101"], written);
+
+        let library = store.load().unwrap();
+        assert_eq!(100, library.unread().articles.len());
+        assert_eq!("101", library.bag()[50]);
+        assert_eq!(vec!["rust"], library.read().articles["1"].tags);
+        assert_eq!(1, store.search("\"101\"").unwrap().len());
+    }
+
+    #[test]
+    fn refuses_encryption() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Configuration {
+            database_file: home.path().join("library.sqlite3"),
+            encryption: true,
+            ..Default::default()
+        };
+
+        match SqliteStore::open(&config) {
+            Err(PickpocketError::InvalidConfiguration(_)) => {}
+            other => panic!("Expected an invalid configuration, got {:?}", other.err()),
+        }
+        assert!(!config.database_file.exists());
+    }
+
+    #[test]
+    fn imports_again_after_a_failed_import() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Configuration {
+            library_file: home.path().join("library_file"),
+            history_file: home.path().join("history"),
+            database_file: home.path().join("library.sqlite3"),
+            ..Default::default()
+        };
+
+        std::fs::write(&config.library_file, "unread: [").unwrap();
+        assert!(SqliteStore::open(&config).is_err());
+        assert!(config.database_file.exists());
+
+        let yaml = "{read: {articles: {}}, unread: {articles: {'1': {id: '1', url: 'https://example.com/1', title: 'Rust'}}}}";
+        std::fs::write(&config.library_file, yaml).unwrap();
+        let store = SqliteStore::open(&config).unwrap();
+        assert_eq!(1, store.load().unwrap().unread().articles.len());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::articles::library::Library;
+use crate::articles::migration::{self, SCHEMA_VERSION};
+#[cfg(feature = "sqlite")]
+use crate::articles::sqlite::SqliteStore;
+use crate::configuration::Configuration;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use crate::files::write_atomically;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+
+/// Libraries kept by [`MemoryStore`], keyed by their `library_file`.
+static MEMORY: Mutex<BTreeMap<PathBuf, Library>> = Mutex::new(BTreeMap::new());
+
+/// Where [`Library`] keeps its articles.
+pub trait LibraryStore {
+    /// Loads the library, creating an empty one if there is none yet.
+    fn load(&self) -> Result<Library>;
+
+    fn save(&self, library: &Library) -> Result<()>;
+
+    /// Loads the library, applies `change` and saves the outcome. Nothing is
+    /// saved when `change` fails. Implementations must call `change` once.
+    fn update(&self, change: &mut dyn FnMut(&mut Library) -> Result<()>) -> Result<()> {
+        let mut library = self.load()?;
+        change(&mut library)?;
+        self.save(&library)
+    }
+}
+
+/// The [`LibraryStore`] backends the configuration can choose from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Storage {
+    /// A YAML file at `library_file`.
+    #[default]
+    Yaml,
+    /// A JSON file at `library_file`.
+    Json,
+    /// Kept in memory for as long as the process runs, under the name of
+    /// `library_file`. Meant for tests.
+    Memory,
+    /// A SQLite database at `database_file`, which also keeps the history.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "yaml" => Ok(Storage::Yaml),
+            "json" => Ok(Storage::Json),
+            "memory" => Ok(Storage::Memory),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(Storage::Sqlite),
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => Err("This build has no SQLite support (see the sqlite feature)".to_owned()),
+            _ => Err(format!(
+                "Unknown storage: {} (expected yaml, json, memory or sqlite)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Storage::Yaml => "yaml",
+            Storage::Json => "json",
+            Storage::Memory => "memory",
+            #[cfg(feature = "sqlite")]
+            Storage::Sqlite => "sqlite",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Storage {
+    /// The store of the library configured on `config`.
+    pub fn open(self, config: &Configuration) -> Result<Box<dyn LibraryStore>> {
+        let path = config.library_file.clone();
+        let cipher = Cipher::new(config);
+
+        Ok(match self {
+            Storage::Yaml => Box::new(YamlStore { path, cipher }),
+            Storage::Json => Box::new(JsonStore { path, cipher }),
+            Storage::Memory => Box::new(MemoryStore { path }),
+            #[cfg(feature = "sqlite")]
+            Storage::Sqlite => Box::new(SqliteStore::open(config)?),
+        })
+    }
+
+    /// The library file as is, before any upgrade, for storages keeping one.
+    pub(crate) fn read_raw(self, config: &Configuration) -> Result<Option<Value>> {
+        let path = &config.library_file;
+        let cipher = Cipher::new(config);
+
+        match self {
+            Storage::Yaml | Storage::Json if !path.exists() => Ok(None),
+            Storage::Yaml => serde_yaml::from_str(&cipher.read_to_string(path)?)
+                .map(Some)
+                .map_err(PickpocketError::corrupt_library),
+            Storage::Json => serde_json::from_str(&cipher.read_to_string(path)?)
+                .map(Some)
+                .map_err(PickpocketError::corrupt_library),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The library as a YAML file, encrypted by `cipher` when encryption is on.
+#[derive(Debug, Clone)]
+pub struct YamlStore {
+    pub path: PathBuf,
+    pub cipher: Cipher,
+}
+
+impl LibraryStore for YamlStore {
+    fn load(&self) -> Result<Library> {
+        if !Path::new(&self.path).exists() {
+            self.save(&Library::new())?;
+        }
+
+        let content = self.cipher.read_to_string(&self.path)?;
+        let library = match serde_yaml::from_str::<Library>(&content) {
+            Ok(library) if library.schema_version() == SCHEMA_VERSION => library,
+            parsed => self.upgrade(
+                parsed.map_err(PickpocketError::corrupt_library),
+                serde_yaml::from_str(&content).map_err(PickpocketError::corrupt_library)?,
+            )?,
+        };
+        self.seal(library)
+    }
+
+    fn save(&self, library: &Library) -> Result<()> {
+        let library_string = serde_yaml::to_string(library)?;
+
+        write_atomically(&self.path, self.cipher.seal(library_string.as_bytes())?)?;
+        Ok(())
+    }
+}
+
+/// The library as a JSON file, encrypted by `cipher` when encryption is on.
+#[derive(Debug, Clone)]
+pub struct JsonStore {
+    pub path: PathBuf,
+    pub cipher: Cipher,
+}
+
+impl LibraryStore for JsonStore {
+    fn load(&self) -> Result<Library> {
+        if !Path::new(&self.path).exists() {
+            self.save(&Library::new())?;
+        }
+
+        let content = self.cipher.read_to_string(&self.path)?;
+        let library = match serde_json::from_str::<Library>(&content) {
+            Ok(library) if library.schema_version() == SCHEMA_VERSION => library,
+            parsed => self.upgrade(
+                parsed.map_err(PickpocketError::corrupt_library),
+                serde_json::from_str(&content).map_err(PickpocketError::corrupt_library)?,
+            )?,
+        };
+        self.seal(library)
+    }
+
+    fn save(&self, library: &Library) -> Result<()> {
+        let library_string = serde_json::to_string(library)?;
+
+        write_atomically(&self.path, self.cipher.seal(library_string.as_bytes())?)?;
+        Ok(())
+    }
+}
+
+/// Library files, possibly written by older releases.
+trait FileStore: LibraryStore {
+    fn path(&self) -> &Path;
+
+    fn cipher(&self) -> &Cipher;
+
+    /// Writes `library` again if it was loaded from a plaintext file while
+    /// encryption is on.
+    fn seal(&self, library: Library) -> Result<Library> {
+        if self.cipher().needs_sealing(self.path()) {
+            self.save(&library)?;
+        }
+        Ok(library)
+    }
+
+    /// Upgrades the `raw` library on file to the current schema, saving it.
+    /// `parsed` is how reading it as is went.
+    fn upgrade(&self, parsed: Result<Library>, raw: Value) -> Result<Library> {
+        if migration::version(&raw) == SCHEMA_VERSION {
+            return parsed;
+        }
+
+        let (library, _) = migration::upgrade_file(self.path(), raw, self.cipher(), |library| {
+            self.save(library)
+        })?;
+        Ok(library)
+    }
+}
+
+impl FileStore for YamlStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cipher(&self) -> &Cipher {
+        &self.cipher
+    }
+}
+
+impl FileStore for JsonStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cipher(&self) -> &Cipher {
+        &self.cipher
+    }
+}
+
+/// The library in memory, shared by every store opened on the same `path`.
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    pub path: PathBuf,
+}
+
+impl MemoryStore {
+    fn libraries() -> MutexGuard<'static, BTreeMap<PathBuf, Library>> {
+        // A panic while holding the lock leaves the libraries as they were
+        MEMORY
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl LibraryStore for MemoryStore {
+    fn load(&self) -> Result<Library> {
+        Ok(MemoryStore::libraries()
+            .entry(self.path.clone())
+            .or_default()
+            .clone())
+    }
+
+    fn save(&self, library: &Library) -> Result<()> {
+        MemoryStore::libraries().insert(self.path.clone(), library.clone());
+        Ok(())
+    }
+
+    /// Holds the lock throughout, so concurrent updates don't overwrite
+    /// each other.
+    fn update(&self, change: &mut dyn FnMut(&mut Library) -> Result<()>) -> Result<()> {
+        let mut libraries = MemoryStore::libraries();
+        let mut library = libraries.get(&self.path).cloned().unwrap_or_default();
+
+        change(&mut library)?;
+        libraries.insert(self.path.clone(), library);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::library::Library;
+    use crate::articles::store::Storage;
+    use crate::configuration::Configuration;
+    use crate::error::PickpocketError;
+
+    #[test]
+    fn saves_only_successful_updates() {
+        let home = tempfile::tempdir().unwrap();
+
+        for &storage in &[Storage::Yaml, Storage::Json, Storage::Memory] {
+            let config = Configuration {
+                library_file: home.path().join(format!("library_{}", storage)),
+                storage,
+                ..Default::default()
+            };
+            let store = storage.open(&config).unwrap();
+            assert_eq!(None, store.load().unwrap().since());
+
+            store
+                .update(&mut |library: &mut Library| {
+                    *library = serde_json::from_str(
+                        r#"{"read": {"articles": {}}, "unread": {"articles": {}}, "since": 10}"#,
+                    )?;
+                    Ok(())
+                })
+                .unwrap();
+            let failed = store.update(&mut |library: &mut Library| {
+                *library = Library::new();
+                Err(PickpocketError::InvalidArgument("nope".to_owned()))
+            });
+
+            assert!(failed.is_err());
+            assert_eq!(Some(10), store.load().unwrap().since(), "{}", storage);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use crate::articles::article::Article;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+static SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// How [`crate::Library::pick`] chooses among unread articles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Every article has the same chance.
+    #[default]
+    Uniform,
+    /// Chances follow the configured [`Weights`].
+    Weighted,
+    /// The article saved the longest ago.
+    Oldest,
+    /// The article saved most recently.
+    Newest,
+    /// Works through a persistent random permutation of the unread articles
+    /// (the library's shuffle bag), so every article comes up within one
+    /// pass. Articles are expected in bag order.
+    ShuffleBag,
+}
+
+/// Groups that multi-article picks are spread across: each pick comes from a
+/// group not picked yet, until every group was picked once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diversity {
+    /// The site an article comes from.
+    Domain,
+    /// The tags of an article (untagged articles form a group of their own).
+    Tag,
+    /// Whether an article is a video, an article, an image or something else.
+    ContentType,
+}
+
+/// Shapes the chances of the [`Strategy::Weighted`] strategy. Every article
+/// starts with a weight of 1, which each setting then multiplies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    /// Extra weight per year since the article was saved (0 ignores age).
+    pub age: f64,
+    /// Multiplier for favorite articles.
+    pub favorite: f64,
+    /// Exponent applied to `1 + reading minutes / 10`: positive values prefer
+    /// long reads, negative ones short reads, 0 ignores length.
+    pub length: f64,
+    /// Multiplier per tag, e.g. `rust = 2.0`. Untagged weights are left alone.
+    pub tags: HashMap<String, f64>,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            age: 1.0,
+            favorite: 2.0,
+            length: 0.0,
+            tags: HashMap::new(),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "uniform" => Ok(Strategy::Uniform),
+            "weighted" => Ok(Strategy::Weighted),
+            "oldest" => Ok(Strategy::Oldest),
+            "newest" => Ok(Strategy::Newest),
+            "shuffle" => Ok(Strategy::ShuffleBag),
+            _ => Err(format!(
+                "Unknown strategy: {} (expected uniform, weighted, oldest, newest or shuffle)",
+                value
+            )),
+        }
+    }
+}
+
+impl FromStr for Diversity {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "domain" => Ok(Diversity::Domain),
+            "tag" => Ok(Diversity::Tag),
+            "type" => Ok(Diversity::ContentType),
+            _ => Err(format!(
+                "Unknown diversity: {} (expected domain, tag or type)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Diversity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Diversity::Domain => "domain",
+            Diversity::Tag => "tag",
+            Diversity::ContentType => "type",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Strategy::Uniform => "uniform",
+            Strategy::Weighted => "weighted",
+            Strategy::Oldest => "oldest",
+            Strategy::Newest => "newest",
+            Strategy::ShuffleBag => "shuffle",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Strategy {
+    /// Chooses one of `articles`. `now` is the current Unix timestamp, used to
+    /// age articles, and `words_per_minute` the reading speed lengths are
+    /// weighed at.
+    pub fn choose<'a, R: Rng + ?Sized>(
+        self,
+        articles: &[&'a Article],
+        weights: &Weights,
+        now: i64,
+        words_per_minute: u64,
+        rng: &mut R,
+    ) -> Option<&'a Article> {
+        match self {
+            Strategy::Uniform => articles.choose(rng).copied(),
+            Strategy::Weighted => articles
+                .choose_weighted(rng, |article| {
+                    weights.weight(article, now, words_per_minute)
+                })
+                // Every weight is 0 (or invalid): fall back to uniform
+                .or_else(|_| articles.choose(rng).ok_or(()))
+                .ok()
+                .copied(),
+            // Articles without a date count as the newest ones
+            Strategy::Oldest => articles
+                .iter()
+                .min_by_key(|article| article.time_added.unwrap_or(i64::MAX))
+                .copied(),
+            Strategy::Newest => articles
+                .iter()
+                .max_by_key(|article| article.time_added.unwrap_or(i64::MAX))
+                .copied(),
+            Strategy::ShuffleBag => articles.first().copied(),
+        }
+    }
+
+    /// Orders `articles` the way this strategy would pick them one after the
+    /// other.
+    pub fn order<'a, R: Rng + ?Sized>(
+        self,
+        articles: &[&'a Article],
+        weights: &Weights,
+        now: i64,
+        words_per_minute: u64,
+        rng: &mut R,
+    ) -> Vec<&'a Article> {
+        let mut ordered = articles.to_vec();
+
+        match self {
+            Strategy::Uniform => ordered.shuffle(rng),
+            Strategy::Weighted => {
+                // Sorting by u^(1/weight) gives a weighted random order
+                // (Efraimidis-Spirakis); articles without weight go last
+                let mut keyed: Vec<(f64, &Article)> = ordered
+                    .into_iter()
+                    .map(
+                        |article| match weights.weight(article, now, words_per_minute) {
+                            weight if weight > 0.0 && weight.is_finite() => {
+                                (rng.gen::<f64>().powf(1.0 / weight), article)
+                            }
+                            _ => (-1.0, article),
+                        },
+                    )
+                    .collect();
+                keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+                ordered = keyed.into_iter().map(|(_, article)| article).collect();
+            }
+            Strategy::Oldest => {
+                ordered.sort_by_key(|article| article.time_added.unwrap_or(i64::MAX))
+            }
+            Strategy::Newest => {
+                ordered.sort_by_key(|article| Reverse(article.time_added.unwrap_or(i64::MAX)))
+            }
+            Strategy::ShuffleBag => {}
+        }
+
+        ordered
+    }
+
+    /// Whether picks following this strategy change from one draw to the next.
+    pub fn is_random(self) -> bool {
+        matches!(self, Strategy::Uniform | Strategy::Weighted)
+    }
+}
+
+impl Diversity {
+    /// Groups `article` belongs to.
+    pub fn groups(self, article: &Article) -> Vec<String> {
+        match self {
+            Diversity::Domain => vec![article.site().unwrap_or_default().to_lowercase()],
+            Diversity::Tag if article.tags.is_empty() => vec![String::new()],
+            Diversity::Tag => article.tags.iter().map(|tag| tag.to_lowercase()).collect(),
+            Diversity::ContentType => {
+                let content_type = if article.has_video {
+                    "video"
+                } else if article.is_article {
+                    "article"
+                } else if article.has_image {
+                    "image"
+                } else {
+                    "other"
+                };
+                vec![content_type.to_owned()]
+            }
+        }
+    }
+
+    /// Whether none of the groups of `article` was `used` yet.
+    pub fn is_fresh(self, article: &Article, used: &HashSet<String>) -> bool {
+        self.groups(article)
+            .iter()
+            .all(|group| !used.contains(group))
+    }
+}
+
+impl Weights {
+    /// Weight of `article` at the `now` Unix timestamp, reading at
+    /// `words_per_minute`.
+    pub fn weight(&self, article: &Article, now: i64, words_per_minute: u64) -> f64 {
+        let mut weight = 1.0;
+
+        if let Some(time_added) = article.time_added {
+            let years = (now - time_added).max(0) as f64 / SECONDS_PER_YEAR;
+            weight *= 1.0 + self.age * years;
+        }
+
+        if article.favorite {
+            weight *= self.favorite;
+        }
+
+        if let Some(minutes) = article.reading_minutes(words_per_minute) {
+            weight *= (1.0 + minutes as f64 / 10.0).powf(self.length);
+        }
+
+        for tag in &article.tags {
+            if let Some(multiplier) = self.tags.get(tag) {
+                weight *= multiplier;
+            }
+        }
+
+        weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::articles::article::{Article, WORDS_PER_MINUTE};
+    use crate::articles::strategy::{Diversity, Strategy, Weights};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn article(id: &str, time_added: Option<i64>) -> Article {
+        Article {
+            id: id.to_owned(),
+            time_added,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn weighs_age_favorites_length_and_tags() {
+        let mut weights = Weights {
+            length: -1.0,
+            ..Default::default()
+        };
+        weights.tags.insert("rust".to_owned(), 3.0);
+
+        let year = 365 * 24 * 60 * 60;
+        let mut old = article("1", Some(0));
+        assert_eq!(2.0, weights.weight(&old, year, WORDS_PER_MINUTE));
+
+        old.favorite = true;
+        old.tags = vec!["rust".to_owned(), "other".to_owned()];
+        old.reading_time = Some(10);
+        assert_eq!(6.0, weights.weight(&old, year, WORDS_PER_MINUTE));
+
+        // Lengths follow the reading speed
+        old.word_count = Some(2000);
+        assert!((weights.weight(&old, year, 100) - 4.0).abs() < 1e-9);
+        assert!((weights.weight(&old, year, 1000) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn picks_by_date_or_weight() {
+        let oldest = article("1", Some(10));
+        let newest = article("2", Some(20));
+        let undated = article("3", None);
+        let articles = vec![&oldest, &newest, &undated];
+        let weights = Weights::default();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let choose = |strategy: Strategy, rng: &mut StdRng| {
+            strategy
+                .choose(&articles, &weights, 30, WORDS_PER_MINUTE, rng)
+                .unwrap()
+                .id
+                .clone()
+        };
+        assert_eq!("1", choose(Strategy::Oldest, &mut rng));
+        assert_eq!("3", choose(Strategy::Newest, &mut rng));
+
+        let mut weights = Weights::default();
+        weights.tags.insert("never".to_owned(), 0.0);
+        let mut tagged = article("4", None);
+        tagged.tags = vec!["never".to_owned()];
+        for _ in 0..20 {
+            let chosen = Strategy::Weighted.choose(
+                &[&tagged, &oldest],
+                &weights,
+                30,
+                WORDS_PER_MINUTE,
+                &mut rng,
+            );
+            assert_eq!("1", chosen.unwrap().id);
+        }
+        assert!(Strategy::Weighted
+            .choose(&[], &weights, 30, WORDS_PER_MINUTE, &mut rng)
+            .is_none());
+    }
+
+    #[test]
+    fn groups_articles_for_diverse_picks() {
+        let mut video = article("1", None);
+        video.url = "https://www.youtube.com/watch".to_owned();
+        video.has_video = true;
+        video.is_article = true;
+        video.tags = vec!["Talks".to_owned(), "rust".to_owned()];
+
+        assert_eq!(vec!["youtube.com"], Diversity::Domain.groups(&video));
+        assert_eq!(vec!["talks", "rust"], Diversity::Tag.groups(&video));
+        assert_eq!(vec!["video"], Diversity::ContentType.groups(&video));
+        assert_eq!(vec![""], Diversity::Tag.groups(&article("2", None)));
+
+        let used = vec!["rust".to_owned()].into_iter().collect();
+        assert!(!Diversity::Tag.is_fresh(&video, &used));
+        assert!(Diversity::Domain.is_fresh(&video, &used));
+    }
+
+    #[test]
+    fn orders_articles_like_successive_picks() {
+        let oldest = article("1", Some(10));
+        let newest = article("2", Some(20));
+        let undated = article("3", None);
+        let articles = vec![&newest, &undated, &oldest];
+        let weights = Weights::default();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let ids = |strategy: Strategy, rng: &mut StdRng| -> Vec<String> {
+            strategy
+                .order(&articles, &weights, 30, WORDS_PER_MINUTE, rng)
+                .into_iter()
+                .map(|article| article.id.clone())
+                .collect()
+        };
+        assert_eq!(vec!["1", "2", "3"], ids(Strategy::Oldest, &mut rng));
+        assert_eq!(vec!["3", "2", "1"], ids(Strategy::Newest, &mut rng));
+
+        let mut uniform = ids(Strategy::Uniform, &mut rng);
+        uniform.sort();
+        assert_eq!(vec!["1", "2", "3"], uniform);
+    }
+}
@@ -1,19 +1,22 @@
 use crate::authentication::token_handler::TokenHandler;
 use crate::configuration::Configuration;
-use crate::logger;
+use crate::error::{PickpocketError, Result};
 
+/// Pocket's two step OAuth flow.
 pub struct OAuth {}
 
 impl OAuth {
-    pub fn request_authorization() {
+    /// 1st step: requests an OAuth token from Pocket and saves it, returning the
+    /// URL the user must visit to approve Pickpocket.
+    pub fn request_authorization(configuration: &Configuration) -> Result<String> {
         // Create a runtime for async operations
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(Self::request_authorization_async());
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(Self::request_authorization_async(configuration))
     }
 
-    async fn request_authorization_async() {
-        let token_handler = TokenHandler::new();
-        let configuration = Configuration::default();
+    /// Async version of [`OAuth::request_authorization`].
+    pub async fn request_authorization_async(configuration: &Configuration) -> Result<String> {
+        let token_handler = TokenHandler::with_configuration(configuration.clone());
         let (auth_url, oauth_url, consumer_key, pocket_homepage) = (
             &configuration.pocket_user_authorize_url,
             &configuration.pocket_oauth_request_url,
@@ -26,99 +29,70 @@ impl OAuth {
             ("consumer_key", consumer_key),
             ("redirect_uri", pocket_homepage),
         ];
-        let response = reqwest::Client::new()
-            .post(oauth_url)
-            .form(&params)
-            .send()
-            .await;
-
-        let response_token = match response {
-            Ok(response) => match response.text().await {
-                Ok(response_text) => {
-                    let mut parse = url::form_urlencoded::parse(response_text.as_bytes());
-                    match parse.next() {
-                        Some((_code, response_token)) => response_token.to_string(),
-                        None => {
-                            logger::log("Invalid response format from Pocket");
-                            "Error".to_owned()
-                        }
-                    }
-                }
-                Err(e) => {
-                    logger::log(&format!("Error reading response: {}", e));
-                    "Error".to_owned()
-                }
-            },
-            Err(e) => {
-                logger::log(&format!("Could not connect to Pocket: {}", e));
-                "Error".to_owned()
-            }
-        };
-
-        if response_token == "Error" {
-            return;
-        }
+        let response_token = Self::request_token(oauth_url, &params).await?;
 
-        // Open auth on browser
+        // Build the URL the user has to open on the browser
         let query_string = format!(
             "request_token={}&redirect_uri={}",
             response_token, pocket_homepage
         );
-        let mut open_on_browser_url = url::Url::parse(auth_url).unwrap();
+        let mut open_on_browser_url = url::Url::parse(auth_url).map_err(|_| {
+            PickpocketError::MissingConfiguration(format!(
+                "Invalid authorization URL: {}",
+                auth_url
+            ))
+        })?;
         open_on_browser_url.set_query(Some(&query_string));
-        open::that(open_on_browser_url.into_string()).ok();
 
         // Save OAuth token on file
-        token_handler.save_oauth(&response_token);
+        token_handler.save_oauth(&response_token)?;
+
+        Ok(open_on_browser_url.into_string())
     }
 
-    pub fn authorize() {
+    /// 2nd step: exchanges the saved OAuth token for an authorization token,
+    /// saving and returning it.
+    pub fn authorize(configuration: &Configuration) -> Result<String> {
         // Create a runtime for async operations
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(Self::authorize_async());
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(Self::authorize_async(configuration))
     }
 
-    async fn authorize_async() {
-        let token_handler = TokenHandler::new();
-        let configuration = Configuration::default();
+    /// Async version of [`OAuth::authorize`].
+    pub async fn authorize_async(configuration: &Configuration) -> Result<String> {
+        let token_handler = TokenHandler::with_configuration(configuration.clone());
         let (uri, consumer_key, response_token) = (
             &configuration.pocket_oauth_authorize_url,
             &configuration.consumer_key,
-            &token_handler.read_oauth(),
+            &token_handler.read_oauth()?,
         );
 
         // Request authorization token (with OAuth token + consumer key)
-        let params = [("consumer_key", consumer_key), ("code", &response_token)];
-        let response = reqwest::Client::new().post(uri).form(&params).send().await;
-
-        let response_token = match response {
-            Ok(response) => match response.text().await {
-                Ok(response_text) => {
-                    let mut parse = url::form_urlencoded::parse(response_text.as_bytes());
-                    match parse.next() {
-                        Some((_code, response_token)) => response_token.to_string(),
-                        None => {
-                            logger::log("Invalid response format from Pocket");
-                            "Error".to_owned()
-                        }
-                    }
-                }
-                Err(e) => {
-                    logger::log(&format!("Error reading response: {}", e));
-                    "Error".to_owned()
-                }
-            },
-            Err(e) => {
-                logger::log(&format!("Could not connect to Pocket: {}", e));
-                "Error".to_owned()
-            }
-        };
-
-        if response_token == "Error" {
-            return;
-        }
+        let params = [("consumer_key", consumer_key), ("code", response_token)];
+        let response_token = Self::request_token(uri, &params).await?;
 
         // Save authentication token
-        token_handler.save_auth(&response_token);
+        token_handler.save_auth(&response_token)?;
+
+        Ok(response_token)
+    }
+
+    async fn request_token(url: &str, params: &[(&str, &String)]) -> Result<String> {
+        let response = reqwest::Client::new().post(url).form(params).send().await?;
+
+        if !response.status().is_success() {
+            return Err(PickpocketError::from_response(&response));
+        }
+
+        let response_text = response.text().await?;
+
+        let mut parse = url::form_urlencoded::parse(response_text.as_bytes());
+        match parse.next() {
+            Some((_code, response_token)) => Ok(response_token.to_string()),
+            None => Err(PickpocketError::Pocket {
+                status: 200,
+                message: "Invalid response format from Pocket".to_owned(),
+            }),
+        }
     }
 }
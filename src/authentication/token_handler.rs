@@ -1,12 +1,22 @@
 use crate::configuration::Configuration;
-use crate::logger;
-use std::fs;
-use std::path::PathBuf;
+use crate::crypto::Cipher;
+use crate::error::{PickpocketError, Result};
+use crate::files::{is_exposed, write_privately};
+use std::path::{Path, PathBuf};
 
+/// Reads and writes the OAuth and authorization tokens on the home folder.
+/// Token files are only readable by their owner, and encrypted when
+/// encryption is on.
 pub struct TokenHandler {
     configuration: Configuration,
 }
 
+impl Default for TokenHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TokenHandler {
     pub fn new() -> Self {
         Self {
@@ -14,36 +24,61 @@ impl TokenHandler {
         }
     }
 
-    pub fn save_oauth(&self, token: &str) {
+    pub fn with_configuration(configuration: Configuration) -> Self {
+        Self { configuration }
+    }
+
+    pub fn save_oauth(&self, token: &str) -> Result<()> {
         self.save_token(&self.configuration.oauth_token_file, token)
     }
 
-    pub fn save_auth(&self, token: &str) {
+    pub fn save_auth(&self, token: &str) -> Result<()> {
         self.save_token(&self.configuration.authorization_token_file, token)
     }
 
-    pub fn read_auth(&self) -> String {
-        self.read_token(&self.configuration.authorization_token_file, "Authorization Token file does not exist. Make sure you request authorization before proceeding.")
+    pub fn read_auth(&self) -> Result<String> {
+        self.read_token(
+            &self.configuration.authorization_token_file,
+            "Authorization Token",
+        )
+    }
+
+    pub fn read_oauth(&self) -> Result<String> {
+        self.read_token(&self.configuration.oauth_token_file, "OAuth Token")
     }
 
-    pub fn read_oauth(&self) -> String {
-        self.read_token(&self.configuration.oauth_token_file, "OAuth Token file does not exist. Make sure you request authorization before proceeding.")
+    /// Token files others than their owner can read, e.g. written by older
+    /// releases.
+    pub fn exposed_tokens(&self) -> Vec<PathBuf> {
+        [
+            &self.configuration.oauth_token_file,
+            &self.configuration.authorization_token_file,
+        ]
+        .iter()
+        .filter(|path| is_exposed(path))
+        .map(|path| path.to_path_buf())
+        .collect()
     }
 
-    fn save_token(&self, path: &PathBuf, token: &str) {
-        match fs::write(path, token) {
-            Ok(_) => "ok",
-            Err(_) => logger::log("Could not write to token file"),
-        };
+    fn save_token(&self, path: &Path, token: &str) -> Result<()> {
+        let cipher = Cipher::new(&self.configuration);
+        write_privately(path, cipher.seal(token.as_bytes())?)
     }
 
-    fn read_token(&self, path: &PathBuf, message: &str) -> String {
-        match fs::read_to_string(path) {
-            Ok(content) => content,
-            Err(_) => {
-                logger::log(message);
-                "no-token".to_owned()
+    fn read_token(&self, path: &Path, token: &str) -> Result<String> {
+        let cipher = Cipher::new(&self.configuration);
+        match cipher.read_to_string(path) {
+            Ok(content) if !content.trim().is_empty() => {
+                if cipher.needs_sealing(path) {
+                    self.save_token(path, content.trim())?;
+                }
+                Ok(content.trim().to_owned())
+            }
+            Ok(_) => Err(PickpocketError::MissingToken(token.to_owned())),
+            Err(PickpocketError::Io(ref error)) if error.kind() == std::io::ErrorKind::NotFound => {
+                Err(PickpocketError::MissingToken(token.to_owned()))
             }
+            Err(error) => Err(error),
         }
     }
 }
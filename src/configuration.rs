@@ -1,13 +1,33 @@
-use dirs;
+use crate::articles::api::MAX_PAGE_SIZE;
+use crate::articles::article::WORDS_PER_MINUTE;
+use crate::articles::store::Storage;
+use crate::articles::strategy::{Diversity, Strategy, Weights};
+use crate::crypto::Passphrase;
+use crate::error::{PickpocketError, Result};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+static CONFIG_FILE_NAME: &str = "config.toml";
+static ENV_PREFIX: &str = "PICKPOCKET_";
+/// The only setting the passphrase is read from, besides asking for it.
+static PASSPHRASE_VARIABLE: &str = "PICKPOCKET_PASSPHRASE";
+
+#[derive(Debug, Clone)]
 pub struct Configuration {
     // Files
     pub authorization_token_file: PathBuf,
+    /// SQLite database holding the library with the `sqlite` storage.
+    pub database_file: PathBuf,
     pub home_folder: PathBuf,
+    pub history_file: PathBuf,
     pub library_file: PathBuf,
     pub oauth_token_file: PathBuf,
+    pub outbox_file: PathBuf,
+    /// Folder keeping a snapshot of the library before every renew, delete and restore.
+    pub snapshots_folder: PathBuf,
+    /// Backend keeping the library at `library_file`.
+    pub storage: Storage,
 
     // Pocket
     pub consumer_key: String,
@@ -17,6 +37,30 @@ pub struct Configuration {
     pub pocket_retrieve_url: String,
     pub pocket_send_url: String,
     pub pocket_user_authorize_url: String,
+
+    // Retrieval
+    pub page_size: usize,
+    pub max_concurrent_requests: usize,
+
+    // Outbox
+    pub max_attempts: u32,
+
+    // Snapshots
+    /// How many snapshots are kept, dropping the oldest ones.
+    pub snapshot_retention: usize,
+
+    // Encryption
+    /// Whether token and library files are written encrypted with `passphrase`.
+    pub encryption: bool,
+    /// Unlocks encrypted files; usually given through `PICKPOCKET_PASSPHRASE`.
+    pub passphrase: Option<Passphrase>,
+
+    // Picking
+    pub strategy: Strategy,
+    pub weights: Weights,
+    pub words_per_minute: u64,
+    /// Groups multi-article picks are spread across, if any.
+    pub diversity: Option<Diversity>,
 }
 
 impl Default for Configuration {
@@ -24,9 +68,14 @@ impl Default for Configuration {
         Configuration {
             // Files
             authorization_token_file: Self::home_folder().join("authorization_token"),
+            database_file: Self::home_folder().join("library.sqlite3"),
             home_folder: Self::home_folder(),
+            history_file: Self::home_folder().join("history"),
             library_file: Self::home_folder().join("library_file"),
             oauth_token_file: Self::home_folder().join("oauth_token"),
+            outbox_file: Self::home_folder().join("outbox"),
+            snapshots_folder: Self::home_folder().join("snapshots"),
+            storage: Storage::default(),
             // Pocket
             consumer_key: env::var("POCKET_CONSUMER_KEY")
                 .unwrap_or("58132-f824d5fbf935681e22e86a3c".to_owned()),
@@ -36,25 +85,316 @@ impl Default for Configuration {
             pocket_retrieve_url: "https://getpocket.com/v3/get".to_owned(),
             pocket_send_url: "https://getpocket.com/v3/send".to_owned(),
             pocket_user_authorize_url: "https://getpocket.com/auth/authorize".to_owned(),
+            // Retrieval
+            page_size: 30,
+            max_concurrent_requests: 5,
+            // Outbox
+            max_attempts: 5,
+            // Snapshots
+            snapshot_retention: 10,
+            // Encryption
+            encryption: false,
+            passphrase: None,
+            // Picking
+            strategy: Strategy::default(),
+            weights: Weights::default(),
+            words_per_minute: WORDS_PER_MINUTE,
+            diversity: None,
         }
     }
 }
 
 impl Configuration {
     pub fn home_folder() -> PathBuf {
-        dirs::home_dir().unwrap().join(".pickpocket")
+        dirs::home_dir().unwrap_or_default().join(".pickpocket")
+    }
+
+    /// Builds the configuration layering defaults < config file < environment.
+    ///
+    /// The config file is `config_file` when given (and must then exist),
+    /// otherwise `$PICKPOCKET_CONFIG` or `~/.pickpocket/config.toml`, if present.
+    /// Command line overrides are applied afterwards through [`Configuration::set`].
+    pub fn load(config_file: Option<&Path>) -> Result<Configuration> {
+        let mut configuration = Configuration::default();
+
+        let (path, required) = match (config_file, env::var_os("PICKPOCKET_CONFIG")) {
+            (Some(path), _) => (path.to_path_buf(), true),
+            (None, Some(path)) => (PathBuf::from(path), true),
+            (None, None) => (Self::home_folder().join(CONFIG_FILE_NAME), false),
+        };
+
+        if required || path.exists() {
+            let content = std::fs::read_to_string(&path).map_err(|_| {
+                PickpocketError::MissingConfiguration(format!(
+                    "Could not read configuration file {}",
+                    path.display()
+                ))
+            })?;
+            configuration.apply_file(&content)?;
+        }
+
+        configuration.apply_env(env::vars())?;
+
+        if dirs::home_dir().is_none() && configuration.home_folder.is_relative() {
+            return Err(PickpocketError::MissingConfiguration(
+                "Could not find your home directory. Set home_folder on your configuration file"
+                    .to_owned(),
+            ));
+        }
+
+        Ok(configuration)
+    }
+
+    /// Applies the settings of a TOML configuration file. Nested tables are
+    /// addressed with dotted keys.
+    pub fn apply_file(&mut self, content: &str) -> Result<()> {
+        let table: toml::value::Table = toml::from_str(content).map_err(|e| {
+            PickpocketError::InvalidConfiguration(format!("Invalid configuration file: {}", e))
+        })?;
+
+        let mut settings = Vec::new();
+        Self::flatten("", toml::Value::Table(table), &mut settings);
+
+        self.apply(settings, true)
+    }
+
+    /// Applies `PICKPOCKET_<KEY>` environment variables (e.g. `PICKPOCKET_PAGE_SIZE`),
+    /// plus the historical `POCKET_CONSUMER_KEY` and `PICKPOCKET_PASSPHRASE`.
+    pub fn apply_env<I: IntoIterator<Item = (String, String)>>(&mut self, vars: I) -> Result<()> {
+        let mut passphrase = None;
+        let settings = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                if name == "POCKET_CONSUMER_KEY" {
+                    return Some(("consumer_key".to_owned(), value));
+                }
+                if name == PASSPHRASE_VARIABLE {
+                    passphrase = Some(value);
+                    return None;
+                }
+
+                let key = name.strip_prefix(ENV_PREFIX)?;
+                Some((key.to_lowercase().replace("__", "."), value))
+            })
+            .collect();
+
+        // Other PICKPOCKET_ variables (like PICKPOCKET_CONFIG) are not settings
+        self.apply(settings, false)?;
+
+        match passphrase {
+            Some(passphrase) => self.set_passphrase(&passphrase),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets the passphrase unlocking encrypted files. It is not a setting like
+    /// the others, so it never ends up on a file or a command line.
+    pub fn set_passphrase(&mut self, passphrase: &str) -> Result<()> {
+        if passphrase.is_empty() {
+            return Err(PickpocketError::InvalidConfiguration(
+                "passphrase must not be empty".to_owned(),
+            ));
+        }
+        self.passphrase = Some(Passphrase::new(passphrase));
+        Ok(())
+    }
+
+    /// Sets a single configuration value from its textual representation.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if key == "passphrase" {
+            return Err(PickpocketError::InvalidConfiguration(format!(
+                "The passphrase can only be given through {}, or when asked for",
+                PASSPHRASE_VARIABLE
+            )));
+        }
+        if self.assign(key, value)? {
+            Ok(())
+        } else {
+            Err(PickpocketError::InvalidConfiguration(format!(
+                "Unknown configuration key: {}",
+                key
+            )))
+        }
+    }
+
+    fn assign(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "authorization_token_file" => self.authorization_token_file = Self::path(value),
+            "database_file" => self.database_file = Self::path(value),
+            "home_folder" => {
+                let home_folder = Self::path(value);
+
+                // File locations follow the home folder, unless set elsewhere
+                for file in [
+                    &mut self.authorization_token_file,
+                    &mut self.database_file,
+                    &mut self.history_file,
+                    &mut self.library_file,
+                    &mut self.oauth_token_file,
+                    &mut self.outbox_file,
+                    &mut self.snapshots_folder,
+                ] {
+                    if let Ok(relative) = file.strip_prefix(&self.home_folder) {
+                        *file = home_folder.join(relative);
+                    }
+                }
+
+                self.home_folder = home_folder;
+            }
+            "history_file" => self.history_file = Self::path(value),
+            "library_file" => self.library_file = Self::path(value),
+            "oauth_token_file" => self.oauth_token_file = Self::path(value),
+            "outbox_file" => self.outbox_file = Self::path(value),
+            "snapshots_folder" => self.snapshots_folder = Self::path(value),
+            "storage" => {
+                self.storage = value
+                    .parse()
+                    .map_err(PickpocketError::InvalidConfiguration)?
+            }
+            "consumer_key" => self.consumer_key = value.to_owned(),
+            "pocket_homepage" => self.pocket_homepage = value.to_owned(),
+            "pocket_oauth_authorize_url" => self.pocket_oauth_authorize_url = value.to_owned(),
+            "pocket_oauth_request_url" => self.pocket_oauth_request_url = value.to_owned(),
+            "pocket_retrieve_url" => self.pocket_retrieve_url = value.to_owned(),
+            "pocket_send_url" => self.pocket_send_url = value.to_owned(),
+            "pocket_user_authorize_url" => self.pocket_user_authorize_url = value.to_owned(),
+            "page_size" => {
+                self.page_size = match Self::positive(key, value)? {
+                    page_size if page_size > MAX_PAGE_SIZE => {
+                        return Err(Self::page_size_too_large(page_size))
+                    }
+                    page_size => page_size,
+                }
+            }
+            "max_concurrent_requests" => self.max_concurrent_requests = Self::positive(key, value)?,
+            "max_attempts" => self.max_attempts = Self::positive(key, value)?,
+            "snapshot_retention" => self.snapshot_retention = Self::positive(key, value)?,
+            "encryption" => self.encryption = Self::boolean(key, value)?,
+            "strategy" => {
+                self.strategy = value
+                    .parse()
+                    .map_err(PickpocketError::InvalidConfiguration)?
+            }
+            "diversity" => {
+                self.diversity = match value {
+                    "none" => None,
+                    value => Some(
+                        value
+                            .parse()
+                            .map_err(PickpocketError::InvalidConfiguration)?,
+                    ),
+                }
+            }
+            "words_per_minute" => self.words_per_minute = Self::positive(key, value)?,
+            "weights.age" => self.weights.age = Self::non_negative(key, value)?,
+            "weights.favorite" => self.weights.favorite = Self::non_negative(key, value)?,
+            "weights.length" => self.weights.length = Self::number(key, value)?,
+            _ => match key.strip_prefix("weights.tags.") {
+                Some(tag) if !tag.is_empty() => {
+                    let weight = Self::non_negative(key, value)?;
+                    self.weights.tags.insert(tag.to_owned(), weight);
+                }
+                _ => return Ok(false),
+            },
+        };
+
+        Ok(true)
     }
 
-    pub fn default() -> Self {
-        Self {
-            ..Default::default()
+    fn apply(&mut self, mut settings: Vec<(String, String)>, strict: bool) -> Result<()> {
+        // The home folder goes first, so file locations set on the same layer win
+        settings.sort_by_key(|(key, _)| key != "home_folder");
+
+        for (key, value) in settings {
+            if strict {
+                self.set(&key, &value)?;
+            } else {
+                self.assign(&key, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flatten(prefix: &str, value: toml::Value, settings: &mut Vec<(String, String)>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    let key = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    Self::flatten(&key, value, settings);
+                }
+            }
+            toml::Value::String(value) => settings.push((prefix.to_owned(), value)),
+            value => settings.push((prefix.to_owned(), value.to_string())),
+        }
+    }
+
+    fn path(value: &str) -> PathBuf {
+        match (value.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(relative), Some(home)) => home.join(relative),
+            _ => PathBuf::from(value),
+        }
+    }
+
+    fn number(key: &str, value: &str) -> Result<f64> {
+        match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(PickpocketError::InvalidConfiguration(format!(
+                "{} must be a number, got: {}",
+                key, value
+            ))),
+        }
+    }
+
+    fn non_negative(key: &str, value: &str) -> Result<f64> {
+        match Self::number(key, value)? {
+            number if number >= 0.0 => Ok(number),
+            _ => Err(PickpocketError::InvalidConfiguration(format!(
+                "{} must not be negative, got: {}",
+                key, value
+            ))),
+        }
+    }
+
+    /// The error for a `page_size` above what Pocket sends per page.
+    pub(crate) fn page_size_too_large(page_size: usize) -> PickpocketError {
+        PickpocketError::InvalidConfiguration(format!(
+            "page_size must be at most {}, as Pocket never sends more articles per page, got: {}",
+            MAX_PAGE_SIZE, page_size
+        ))
+    }
+
+    fn boolean(key: &str, value: &str) -> Result<bool> {
+        value.parse().map_err(|_| {
+            PickpocketError::InvalidConfiguration(format!(
+                "{} must be true or false, got: {}",
+                key, value
+            ))
+        })
+    }
+
+    /// Parses a number above zero that fits in `N`.
+    fn positive<N: FromStr + PartialOrd + Default>(key: &str, value: &str) -> Result<N> {
+        match value.parse::<N>() {
+            Ok(number) if number > N::default() => Ok(number),
+            _ => Err(PickpocketError::InvalidConfiguration(format!(
+                "{} must be a positive number, got: {}",
+                key, value
+            ))),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::articles::strategy::Strategy;
     use crate::configuration::Configuration;
+    use crate::crypto::Passphrase;
+    use std::path::PathBuf;
 
     #[test]
     fn allows_consumer_key_configuration_through_env() {
@@ -67,4 +407,98 @@ mod tests {
         let config = Configuration::default();
         assert_eq!("58132-f824d5fbf935681e22e86a3c", config.consumer_key);
     }
+
+    #[test]
+    fn layers_file_then_env_then_overrides() {
+        let mut config = Configuration::default();
+
+        config
+            .apply_file(
+                r#"
+                home_folder = "/srv/pickpocket"
+                pocket_retrieve_url = "http://localhost:8080/v3/get"
+                consumer_key = "from-file"
+                page_size = 10
+                "#,
+            )
+            .unwrap();
+        config
+            .apply_env(vec![
+                ("PICKPOCKET_PAGE_SIZE".to_owned(), "20".to_owned()),
+                ("PICKPOCKET_CONFIG".to_owned(), "/ignored.toml".to_owned()),
+                ("PICKPOCKET_UNRELATED".to_owned(), "ignored".to_owned()),
+                ("HOME".to_owned(), "/ignored".to_owned()),
+            ])
+            .unwrap();
+        config.set("consumer_key", "from-cli").unwrap();
+
+        assert_eq!("http://localhost:8080/v3/get", config.pocket_retrieve_url);
+        assert_eq!(PathBuf::from("/srv/pickpocket"), config.home_folder);
+        assert_eq!(
+            PathBuf::from("/srv/pickpocket/library_file"),
+            config.library_file
+        );
+        assert_eq!(20, config.page_size);
+        assert_eq!("from-cli", config.consumer_key);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_invalid_values() {
+        let mut config = Configuration::default();
+
+        assert!(config.apply_file("library = \"/tmp/library\"").is_err());
+        assert!(config.set("max_concurrent_requests", "0").is_err());
+        assert!(config.set("max_attempts", "4294967297").is_err());
+        assert_eq!(5, config.max_attempts);
+        assert!(config.apply_file("page_size = ").is_err());
+        assert!(config.set("page_size", "31").is_err());
+        assert!(config
+            .apply_env(vec![("PICKPOCKET_PAGE_SIZE".to_owned(), "50".to_owned())])
+            .is_err());
+        assert_eq!(30, config.page_size);
+        assert!(config.set("strategy", "random").is_err());
+        assert!(config.set("weights.favorite", "-1").is_err());
+    }
+
+    #[test]
+    fn takes_the_passphrase_from_the_environment_only() {
+        let mut config = Configuration::default();
+
+        assert!(config.apply_file("passphrase = \"secret\"").is_err());
+        assert!(config.set("passphrase", "secret").is_err());
+        assert_eq!(None, config.passphrase);
+
+        config
+            .apply_env(vec![(
+                "PICKPOCKET_PASSPHRASE".to_owned(),
+                "correct horse".to_owned(),
+            )])
+            .unwrap();
+        assert_eq!(Some(Passphrase::new("correct horse")), config.passphrase);
+    }
+
+    #[test]
+    fn reads_pick_weights_from_the_config_file() {
+        let mut config = Configuration::default();
+
+        config
+            .apply_file(
+                r#"
+                strategy = "weighted"
+
+                [weights]
+                age = 0.5
+                length = -1
+
+                [weights.tags]
+                rust = 2
+                "#,
+            )
+            .unwrap();
+
+        assert_eq!(Strategy::Weighted, config.strategy);
+        assert_eq!(0.5, config.weights.age);
+        assert_eq!(-1.0, config.weights.length);
+        assert_eq!(Some(&2.0), config.weights.tags.get("rust"));
+    }
 }
@@ -0,0 +1,242 @@
+//! Passphrase encryption of the token, library, history and outbox files, and
+//! of the copies kept of them.
+//!
+//! Encrypted files start with [`MAGIC`], followed by the Argon2 salt the key
+//! was derived with, the XChaCha20-Poly1305 nonce and the ciphertext. Files
+//! without the marker are read as plaintext, and encrypted as soon as they
+//! are read with encryption on (see [`Cipher::needs_sealing`]).
+
+use crate::configuration::Configuration;
+use crate::error::{PickpocketError, Result};
+use crate::files::write_privately;
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Marks files encrypted by Pickpocket.
+pub static MAGIC: &[u8] = b"PPCRYPT1";
+const MAGIC_LENGTH: usize = 8;
+static SALT_LENGTH: usize = 16;
+static NONCE_LENGTH: usize = 24;
+
+/// Keys derived so far: deriving one takes a noticeable time on purpose.
+static KEYS: Mutex<Vec<DerivedKey>> = Mutex::new(Vec::new());
+
+struct DerivedKey {
+    passphrase: String,
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+/// The secret encrypted files are locked with. Never shows up in logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: &str) -> Passphrase {
+        Passphrase(passphrase.to_owned())
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Seals and opens files following the `encryption` and `passphrase`
+/// settings.
+#[derive(Debug, Clone, Default)]
+pub struct Cipher {
+    passphrase: Option<Passphrase>,
+    encrypt: bool,
+}
+
+impl Cipher {
+    pub fn new(config: &Configuration) -> Cipher {
+        Cipher {
+            passphrase: config.passphrase.clone(),
+            encrypt: config.encryption,
+        }
+    }
+
+    /// Whether files are written encrypted.
+    pub fn encrypts(&self) -> bool {
+        self.encrypt
+    }
+
+    /// Whether the file at `path` is plaintext while encryption is on, so it
+    /// should be written again.
+    pub fn needs_sealing(&self, path: &Path) -> bool {
+        self.encrypt && path.exists() && !is_encrypted(path)
+    }
+
+    /// Encrypts the file at `path` in place if it is plaintext while
+    /// encryption is on.
+    pub fn seal_file(&self, path: &Path) -> Result<()> {
+        if self.needs_sealing(path) {
+            self.copy(path, path)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the file at `from` to `to`, only readable by its owner. The copy
+    /// is encrypted when encryption is on; encrypted files are copied as is.
+    pub fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let contents = std::fs::read(from)?;
+        if contents.starts_with(MAGIC) {
+            return write_privately(to, contents);
+        }
+        write_privately(to, self.seal(&contents)?)
+    }
+
+    /// Encrypts `contents` when encryption is on, otherwise leaves them as is.
+    pub fn seal(&self, contents: &[u8]) -> Result<Vec<u8>> {
+        if !self.encrypt {
+            return Ok(contents.to_vec());
+        }
+        let passphrase = self.passphrase()?;
+
+        let salt = Cipher::salt_for(passphrase);
+        let key = Cipher::key(passphrase, &salt)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(&nonce, contents)
+            .map_err(|_| PickpocketError::Encryption("Could not encrypt".to_owned()))?;
+
+        let mut sealed =
+            Vec::with_capacity(MAGIC.len() + salt.len() + nonce.len() + ciphertext.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&salt);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts `contents` if they were encrypted, otherwise returns them as is.
+    pub fn open(&self, contents: Vec<u8>) -> Result<Vec<u8>> {
+        if !contents.starts_with(MAGIC) {
+            return Ok(contents);
+        }
+        let passphrase = self.passphrase()?;
+
+        let header = MAGIC.len() + SALT_LENGTH + NONCE_LENGTH;
+        if contents.len() < header {
+            return Err(PickpocketError::Encryption(
+                "Encrypted file is truncated".to_owned(),
+            ));
+        }
+        let salt = &contents[MAGIC.len()..MAGIC.len() + SALT_LENGTH];
+        let nonce = XNonce::from_slice(&contents[MAGIC.len() + SALT_LENGTH..header]);
+
+        let key = Cipher::key(passphrase, salt)?;
+        XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(nonce, &contents[header..])
+            .map_err(|_| {
+                PickpocketError::Encryption(
+                    "Could not decrypt: wrong passphrase, or the file is corrupt".to_owned(),
+                )
+            })
+    }
+
+    /// Reads the file at `path` as text, decrypting it if needed.
+    pub fn read_to_string(&self, path: &Path) -> Result<String> {
+        let contents = self.open(std::fs::read(path)?)?;
+        String::from_utf8(contents)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error).into())
+    }
+
+    fn passphrase(&self) -> Result<&str> {
+        match &self.passphrase {
+            Some(Passphrase(passphrase)) => Ok(passphrase),
+            None => Err(PickpocketError::MissingConfiguration(
+                "Your files are encrypted: set PICKPOCKET_PASSPHRASE or enter your passphrase when asked"
+                    .to_owned(),
+            )),
+        }
+    }
+
+    /// A salt already used with `passphrase`, so its key is derived only once.
+    fn salt_for(passphrase: &str) -> Vec<u8> {
+        let keys = KEYS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(known) = keys.iter().find(|known| known.passphrase == passphrase) {
+            return known.salt.clone();
+        }
+
+        let mut salt = vec![0; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    fn key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+        let mut keys = KEYS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(known) = keys
+            .iter()
+            .find(|known| known.passphrase == passphrase && known.salt == salt)
+        {
+            return Ok(known.key);
+        }
+
+        let mut key = [0; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|error| PickpocketError::Encryption(error.to_string()))?;
+        keys.push(DerivedKey {
+            passphrase: passphrase.to_owned(),
+            salt: salt.to_vec(),
+            key,
+        });
+        Ok(key)
+    }
+}
+
+/// Whether the file at `path` was encrypted by Pickpocket.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut start = [0; MAGIC_LENGTH];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok()
+        && start == MAGIC
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::Configuration;
+    use crate::crypto::{Cipher, Passphrase, MAGIC};
+
+    #[test]
+    fn seals_and_opens_with_the_same_passphrase_only() {
+        let config = Configuration {
+            encryption: true,
+            passphrase: Some(Passphrase::new("correct horse")),
+            ..Default::default()
+        };
+        let cipher = Cipher::new(&config);
+
+        let sealed = cipher.seal(b"reading history").unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(
+            b"reading history".to_vec(),
+            cipher.open(sealed.clone()).unwrap()
+        );
+
+        let wrong = Cipher::new(&Configuration {
+            passphrase: Some(Passphrase::new("battery staple")),
+            ..Default::default()
+        });
+        assert!(wrong.open(sealed.clone()).is_err());
+        assert!(Cipher::default().open(sealed).is_err());
+
+        // Plaintext goes through untouched
+        assert_eq!(
+            b"plain".to_vec(),
+            Cipher::default().open(b"plain".to_vec()).unwrap()
+        );
+        assert_eq!(b"plain".to_vec(), Cipher::default().seal(b"plain").unwrap());
+    }
+}
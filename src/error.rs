@@ -0,0 +1,204 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, PickpocketError>;
+
+/// Everything that can go wrong while talking to Pocket or handling local files.
+#[derive(Debug)]
+pub enum PickpocketError {
+    /// A command line argument could not be understood.
+    InvalidArgument(String),
+    /// Reading or writing a local file failed.
+    Io(std::io::Error),
+    /// A YAML file could not be parsed or serialized.
+    Yaml(serde_yaml::Error),
+    /// The library file (or a snapshot of it) could not be parsed, whatever
+    /// its format.
+    CorruptLibrary(String),
+    /// A Pocket response could not be parsed or a request could not be built.
+    Json(serde_json::Error),
+    /// Pocket could not be reached.
+    Http(reqwest::Error),
+    /// Pocket answered, but with an error.
+    Pocket { status: u16, message: String },
+    /// A token file is missing: the user did not go through authorization yet.
+    MissingToken(String),
+    /// A required configuration value is not available.
+    MissingConfiguration(String),
+    /// The configuration file, environment or overrides hold an invalid setting.
+    InvalidConfiguration(String),
+    /// The library was written by a newer release, with this schema version.
+    UnsupportedSchema(u32),
+    /// An encrypted file could not be decrypted, or a file could not be encrypted.
+    Encryption(String),
+    /// A [`crate::LibraryStore`] did not behave as its contract says.
+    Storage(String),
+    /// The SQLite library could not be read or written.
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl PickpocketError {
+    /// Process exit code for this error, so scripts can tell failures apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PickpocketError::InvalidArgument(_) => 2,
+            PickpocketError::Io(_) => 3,
+            PickpocketError::Yaml(_) => 4,
+            PickpocketError::CorruptLibrary(_) => 4,
+            PickpocketError::Json(_) => 5,
+            PickpocketError::Http(_) => 6,
+            PickpocketError::Pocket { .. } => 7,
+            PickpocketError::MissingToken(_) => 8,
+            PickpocketError::MissingConfiguration(_) => 9,
+            PickpocketError::InvalidConfiguration(_) => 13,
+            PickpocketError::UnsupportedSchema(_) => 11,
+            PickpocketError::Encryption(_) => 12,
+            PickpocketError::Storage(_) => 14,
+            #[cfg(feature = "sqlite")]
+            PickpocketError::Sqlite(_) => 10,
+        }
+    }
+
+    /// Builds a `CorruptLibrary` error from a failure to parse the library.
+    pub(crate) fn corrupt_library<E: fmt::Display>(error: E) -> Self {
+        PickpocketError::CorruptLibrary(error.to_string())
+    }
+
+    /// Builds a `Pocket` error from a non successful response, using the
+    /// `X-Error` header Pocket sends along with it.
+    pub(crate) fn from_response(response: &reqwest::Response) -> Self {
+        let message = response
+            .headers()
+            .get("X-Error")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("Unexpected response from Pocket")
+            .to_owned();
+
+        PickpocketError::Pocket {
+            status: response.status().as_u16(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for PickpocketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PickpocketError::InvalidArgument(message) => write!(f, "{}", message),
+            PickpocketError::Io(error) => write!(f, "File error: {}", error),
+            PickpocketError::Yaml(error) => write!(f, "File is corrupt: {}", error),
+            PickpocketError::CorruptLibrary(message) => {
+                write!(f, "Library file is corrupt: {}", message)
+            }
+            PickpocketError::Json(error) => write!(f, "Invalid JSON: {}", error),
+            PickpocketError::Http(error) => write!(f, "Could not connect to Pocket: {}", error),
+            PickpocketError::Pocket { status, message } => {
+                write!(f, "Pocket returned an error ({}): {}", status, message)
+            }
+            PickpocketError::MissingToken(token) => write!(
+                f,
+                "{} file does not exist. Make sure you request authorization before proceeding.",
+                token
+            ),
+            PickpocketError::MissingConfiguration(message) => write!(f, "{}", message),
+            PickpocketError::InvalidConfiguration(message) => write!(f, "{}", message),
+            PickpocketError::UnsupportedSchema(version) => write!(
+                f,
+                "Library file has schema version {}, which this release can't read. Upgrade Pickpocket.",
+                version
+            ),
+            PickpocketError::Encryption(message) => write!(f, "{}", message),
+            PickpocketError::Storage(message) => write!(f, "Library storage error: {}", message),
+            #[cfg(feature = "sqlite")]
+            PickpocketError::Sqlite(error) => write!(f, "Library database error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PickpocketError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PickpocketError::Io(error) => Some(error),
+            PickpocketError::Yaml(error) => Some(error),
+            PickpocketError::Json(error) => Some(error),
+            PickpocketError::Http(error) => Some(error),
+            #[cfg(feature = "sqlite")]
+            PickpocketError::Sqlite(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PickpocketError {
+    fn from(error: std::io::Error) -> Self {
+        PickpocketError::Io(error)
+    }
+}
+
+impl From<serde_yaml::Error> for PickpocketError {
+    fn from(error: serde_yaml::Error) -> Self {
+        PickpocketError::Yaml(error)
+    }
+}
+
+impl From<serde_json::Error> for PickpocketError {
+    fn from(error: serde_json::Error) -> Self {
+        PickpocketError::Json(error)
+    }
+}
+
+impl From<reqwest::Error> for PickpocketError {
+    fn from(error: reqwest::Error) -> Self {
+        PickpocketError::Http(error)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for PickpocketError {
+    fn from(error: rusqlite::Error) -> Self {
+        PickpocketError::Sqlite(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::PickpocketError;
+    use std::collections::HashSet;
+
+    #[test]
+    fn maps_each_error_kind_to_an_exit_code() {
+        let io = std::io::Error::other("disk");
+        let yaml = serde_yaml::from_str::<u8>("[").unwrap_err();
+        let json = serde_json::from_str::<u8>("[").unwrap_err();
+        let http = reqwest::Client::new().get("not a url").build().unwrap_err();
+
+        let errors = vec![
+            PickpocketError::InvalidArgument("quantity".to_owned()),
+            PickpocketError::Io(io),
+            PickpocketError::Json(json),
+            PickpocketError::Http(http),
+            PickpocketError::Pocket {
+                status: 401,
+                message: "Unauthorized".to_owned(),
+            },
+            PickpocketError::MissingToken("Authorization Token".to_owned()),
+            PickpocketError::MissingConfiguration("home".to_owned()),
+            PickpocketError::InvalidConfiguration("storage".to_owned()),
+            PickpocketError::UnsupportedSchema(3),
+            PickpocketError::Encryption("wrong passphrase".to_owned()),
+            PickpocketError::Storage("change skipped".to_owned()),
+            #[cfg(feature = "sqlite")]
+            PickpocketError::Sqlite(rusqlite::Error::InvalidQuery),
+        ];
+        let codes: HashSet<i32> = errors.iter().map(PickpocketError::exit_code).collect();
+        assert_eq!(errors.len(), codes.len());
+
+        // Shared on purpose: corrupt local files, whichever format the library is kept in
+        let corrupt = PickpocketError::CorruptLibrary("unread: [".to_owned());
+        assert_eq!(4, corrupt.exit_code());
+        assert_eq!(4, PickpocketError::Yaml(yaml).exit_code());
+        assert!(!codes.contains(&4));
+        assert!(!codes.contains(&0));
+        assert!(!codes.contains(&1));
+    }
+}
@@ -0,0 +1,209 @@
+//! Crash-safe writes, owner-only files and the lock serializing commands on
+//! the home folder.
+
+use crate::configuration::Configuration;
+use crate::error::Result;
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+static LOCK_FILE_NAME: &str = "lock";
+
+/// Writes `contents` to a temporary file next to `path`, syncs it to disk and
+/// renames it over `path`. Whatever happens midway, `path` keeps either its
+/// previous contents or the new ones.
+pub fn write_atomically<C: AsRef<[u8]>>(path: &Path, contents: C) -> Result<()> {
+    write(path, contents.as_ref(), false)
+}
+
+/// Like [`write_atomically`], for secrets: on Unix the file is only readable
+/// and writable by its owner.
+pub fn write_privately<C: AsRef<[u8]>>(path: &Path, contents: C) -> Result<()> {
+    write(path, contents.as_ref(), true)
+}
+
+/// Whether others than its owner can access the file at `path`. Always false
+/// on platforms without Unix permissions.
+pub fn is_exposed(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::metadata(path)
+            .map(|metadata| metadata.permissions().mode() & 0o077 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+fn write(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // Unique per write, so threads writing the same file don't share it
+    let temporary = folder.join(format!(
+        ".{}.{}.{:016x}.tmp",
+        name,
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if private {
+            options.mode(0o600);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    let written = options.open(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(error) = written.and_then(|_| fs::rename(&temporary, path)) {
+        fs::remove_file(&temporary).ok();
+        return Err(error.into());
+    }
+
+    // Persist the rename itself; not every platform can sync a folder
+    if let Ok(folder) = File::open(folder) {
+        folder.sync_all().ok();
+    }
+
+    Ok(())
+}
+
+/// Advisory lock on the home folder, so commands changing local files run one
+/// at a time. Released when dropped.
+#[derive(Debug)]
+pub struct HomeLock {
+    file: File,
+}
+
+impl HomeLock {
+    /// Takes the lock, waiting for whoever holds it.
+    pub fn acquire(config: &Configuration) -> Result<HomeLock> {
+        let file = HomeLock::open(config)?;
+        file.lock_exclusive()?;
+        Ok(HomeLock { file })
+    }
+
+    /// Takes the lock if nobody holds it.
+    pub fn try_acquire(config: &Configuration) -> Result<Option<HomeLock>> {
+        let file = HomeLock::open(config)?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(HomeLock { file })),
+            Err(error) if error.kind() == fs2::lock_contended_error().kind() => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// The lock file on `config`'s home folder.
+    pub fn path(config: &Configuration) -> PathBuf {
+        config.home_folder.join(LOCK_FILE_NAME)
+    }
+
+    fn open(config: &Configuration) -> Result<File> {
+        Ok(OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(HomeLock::path(config))?)
+    }
+}
+
+impl Drop for HomeLock {
+    fn drop(&mut self) {
+        FileExt::unlock(&self.file).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::Configuration;
+    use crate::files::{is_exposed, write_atomically, write_privately, HomeLock};
+
+    #[test]
+    fn replaces_files_without_leaving_temporary_ones() {
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join("library_file");
+
+        write_atomically(&path, "old").unwrap();
+        write_atomically(&path, "new").unwrap();
+
+        assert_eq!("new", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(1, std::fs::read_dir(home.path()).unwrap().count());
+    }
+
+    #[test]
+    fn writes_the_same_file_from_several_threads() {
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join("library_file");
+
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        write_atomically(&path, format!("writer {}", writer)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("writer "));
+        assert_eq!(1, std::fs::read_dir(home.path()).unwrap().count());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn writes_private_files_for_the_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join("oauth_token");
+
+        write_atomically(&path, "token").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(is_exposed(&path));
+
+        write_privately(&path, "token").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        assert!(!is_exposed(&path));
+    }
+
+    #[test]
+    fn lets_a_single_holder_lock_the_home_folder() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Configuration {
+            home_folder: home.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let lock = HomeLock::try_acquire(&config).unwrap();
+        assert!(lock.is_some());
+        assert!(HomeLock::try_acquire(&config).unwrap().is_none());
+
+        drop(lock);
+        assert!(HomeLock::try_acquire(&config).unwrap().is_some());
+    }
+}
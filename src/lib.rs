@@ -0,0 +1,42 @@
+//! Pickpocket selects a random article from your [Pocket](https://getpocket.com) library.
+//!
+//! The crate powers the `pickpocket` command line tool, but every operation it
+//! performs is available here as a plain function returning a `Result`:
+//!
+//! - [`OAuth`] runs the two step authorization flow against Pocket
+//! - [`API`] retrieves and archives articles through Pocket's v3 API
+//! - [`Library`] keeps the local read/unread [`Inventory`] of [`Article`]s,
+//!   on the [`LibraryStore`] chosen by the `storage` setting
+//! - [`Outbox`] queues local changes until they can be sent to Pocket
+//!
+//! Nothing in this crate prints to the terminal; logging is left to the caller.
+//! Failures are reported as a [`PickpocketError`].
+
+pub mod articles;
+pub mod authentication;
+pub mod configuration;
+pub mod crypto;
+pub mod error;
+pub mod files;
+pub mod logger;
+
+pub use articles::api::API;
+pub use articles::article::Article;
+pub use articles::filter::Filter;
+pub use articles::history::{History, Opening, Pick};
+pub use articles::inventory::Inventory;
+pub use articles::library::{Library, RenewSummary, Status, Undo};
+pub use articles::migration::{MigrationReport, SCHEMA_VERSION};
+pub use articles::outbox::{Action, Entry, FlushSummary, Outbox};
+pub use articles::response::{Item, ItemStatus, RetrieveResponse, SendResponse};
+pub use articles::snapshot::Snapshot;
+#[cfg(feature = "sqlite")]
+pub use articles::sqlite::SqliteStore;
+pub use articles::store::{JsonStore, LibraryStore, MemoryStore, Storage, YamlStore};
+pub use articles::strategy::{Diversity, Strategy, Weights};
+pub use authentication::oauth::OAuth;
+pub use authentication::token_handler::TokenHandler;
+pub use configuration::Configuration;
+pub use crypto::{Cipher, Passphrase};
+pub use error::{PickpocketError, Result};
+pub use files::HomeLock;
@@ -2,3 +2,9 @@ pub fn log(message: &str) -> &str {
     println!("[Pickpocket] {}", message);
     message
 }
+
+/// Same as [`log`], on stderr, so stdout can be piped into other tools.
+pub fn log_to_stderr(message: &str) -> &str {
+    eprintln!("[Pickpocket] {}", message);
+    message
+}
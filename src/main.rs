@@ -1,11 +1,15 @@
-mod articles;
-mod authentication;
-mod configuration;
-mod logger;
-
-use articles::library::Library;
-use authentication::oauth::OAuth;
-use clap::{App, Arg, SubCommand};
+use chrono::{Local, NaiveDate, TimeZone};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use pickpocket::logger;
+use pickpocket::{
+    Article, Configuration, Filter, History, HomeLock, Library, OAuth, Opening, Outbox,
+    PickpocketError, Result, Snapshot, TokenHandler,
+};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use regex::Regex;
+use std::path::Path;
+use std::str::FromStr;
 
 fn main() {
     let matches =
@@ -13,6 +17,12 @@ fn main() {
             .version(env!("CARGO_PKG_VERSION"))
             .author("Tiago Amaro <tiagopadrela@gmail.com>")
             .about("Selects a random article from your Pocket (former Read It Later)")
+            .arg(
+                Arg::with_name("config").long("config").help("Configuration file to use (default: ~/.pickpocket/config.toml)").takes_value(true).global(true)
+            )
+            .arg(
+                Arg::with_name("set").long("set").short("s").help("Overrides a configuration value, e.g. --set page_size=50").takes_value(true).multiple(true).number_of_values(1).global(true)
+            )
             .subcommand(
                 SubCommand::with_name("oauth")
                     .about("1st authorization step: ask Pocket to allow Pickpocket app"),
@@ -23,7 +33,35 @@ fn main() {
             .subcommand(SubCommand::with_name("pick").about(
                 "Picks a random article from your library (marking it as read)",
             ).arg(
-                Arg::with_name("quantity").short("q").help("Quantity of articles to open").required(true).takes_value(true)
+                Arg::with_name("quantity").short("q").help("Quantity of articles to open").required_unless("budget").takes_value(true)
+            ).arg(
+                Arg::with_name("budget").long("budget").help("Picks articles fitting in this reading time, e.g. 25m or 1h30m").takes_value(true).conflicts_with("quantity")
+            ).arg(
+                Arg::with_name("seed").long("seed").help("Seeds the random picks, so the same library and seed pick the same articles").takes_value(true)
+            ).arg(
+                Arg::with_name("strategy").long("strategy").help("How articles are picked (default: the strategy setting, or uniform)").takes_value(true).possible_values(&["uniform", "weighted", "oldest", "newest", "shuffle"])
+            ).arg(
+                Arg::with_name("tag").long("tag").help("Only picks articles with this tag (repeatable)").takes_value(true).multiple(true).number_of_values(1)
+            ).arg(
+                Arg::with_name("domain").long("domain").help("Only picks articles from this domain (repeatable)").takes_value(true).multiple(true).number_of_values(1)
+            ).arg(
+                Arg::with_name("min-words").long("min-words").help("Only picks articles with at least this many words").takes_value(true)
+            ).arg(
+                Arg::with_name("max-words").long("max-words").help("Only picks articles with at most this many words").takes_value(true)
+            ).arg(
+                Arg::with_name("max-minutes").long("max-minutes").help("Only picks articles read in at most this many minutes").takes_value(true)
+            ).arg(
+                Arg::with_name("title-matches").long("title-matches").help("Only picks articles whose title matches this regular expression").takes_value(true)
+            ).arg(
+                Arg::with_name("diverse").long("diverse").help("Spreads picks across domains (default), tags or content types").takes_value(true).min_values(0).max_values(1).possible_values(&["domain", "tag", "type"])
+            ).arg(
+                Arg::with_name("interactive").long("interactive").short("i").help("Offers to snooze each picked article").conflicts_with_all(&["dry-run", "print"])
+            ).arg(
+                Arg::with_name("dry-run").long("dry-run").help("Shows what would be picked, without marking or opening anything")
+            ).arg(
+                Arg::with_name("no-open").long("no-open").help("Does not open picked articles on the browser")
+            ).arg(
+                Arg::with_name("print").long("print").help("Writes picked articles to stdout").takes_value(true).possible_values(&["url", "title", "json"])
             ))
             .subcommand(SubCommand::with_name("renew").about(
                 "Syncs your local library with your Pocket. It will delete read articles and download new articles from your library",
@@ -31,38 +69,749 @@ fn main() {
             .subcommand(SubCommand::with_name("status").about(
                 "Show the number of read/unread articles you have on your local library",
             ))
+            .subcommand(SubCommand::with_name("history").about(
+                "Lists the articles you picked",
+            ).arg(
+                Arg::with_name("from").long("from").help("Only lists picks from this day on (YYYY-MM-DD)").takes_value(true)
+            ).arg(
+                Arg::with_name("to").long("to").help("Only lists picks up to this day (YYYY-MM-DD)").takes_value(true)
+            ))
+            .subcommand(SubCommand::with_name("undo").about(
+                "Moves the last picked articles back to unread, unless they were already archived on Pocket",
+            ).arg(
+                Arg::with_name("count").help("Number of picks to undo (default: 1)")
+            ))
+            .subcommand(SubCommand::with_name("snooze").about(
+                "Hides an article from picks until a later date",
+            ).arg(
+                Arg::with_name("id").help("Id of the article").required(true)
+            ).arg(
+                Arg::with_name("until").long("until").help("Date (YYYY-MM-DD) or duration (like 3d or 2w) to snooze the article for").required(true).takes_value(true)
+            ))
+            .subcommand(SubCommand::with_name("favorite").about(
+                "Marks an article as favorite (sent to Pocket on the next renew)",
+            ).arg(
+                Arg::with_name("id").help("Id of the article").required(true)
+            ))
+            .subcommand(SubCommand::with_name("tag").about(
+                "Adds tags to an article (sent to Pocket on the next renew)",
+            ).arg(
+                Arg::with_name("id").help("Id of the article").required(true)
+            ).arg(
+                Arg::with_name("tags").help("Tags to add").required(true).multiple(true)
+            ))
+            .subcommand(SubCommand::with_name("delete").about(
+                "Deletes an article from your library (sent to Pocket on the next renew)",
+            ).arg(
+                Arg::with_name("id").help("Id of the article").required(true)
+            ))
+            .subcommand(SubCommand::with_name("add").about(
+                "Saves a URL to your Pocket (sent on the next renew)",
+            ).arg(
+                Arg::with_name("url").help("URL to save").required(true)
+            ).arg(
+                Arg::with_name("title").long("title").help("Title of the article").takes_value(true)
+            ))
+            .subcommand(SubCommand::with_name("outbox").about(
+                "Shows the changes waiting to be sent to Pocket, and the ones that failed",
+            ).arg(
+                Arg::with_name("retry").long("retry").help("Queues failed changes again")
+            ).arg(
+                Arg::with_name("clear").long("clear").help("Forgets failed changes").conflicts_with("retry")
+            ))
+            .subcommand(SubCommand::with_name("snapshots").about(
+                "Manages the copies of your library taken before renew, delete and restore",
+            ).subcommand(SubCommand::with_name("list").about(
+                "Lists snapshots, oldest first",
+            )).subcommand(SubCommand::with_name("restore").about(
+                "Puts back the library and outbox of a snapshot",
+            ).arg(
+                Arg::with_name("id").help("Id of the snapshot").required(true)
+            )))
+            .subcommand(SubCommand::with_name("migrate").about(
+                "Upgrades your library file to the current format, keeping a backup",
+            ).arg(
+                Arg::with_name("check").long("check").help("Only reports what would change")
+            ))
             .get_matches();
 
-    Library::guarantee_home_folder();
+    if let Err(error) = run(&matches) {
+        logger::log(&error.to_string());
+        std::process::exit(error.exit_code());
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let mut config = configuration(matches)?;
+    Library::guarantee_home_folder(&config)?;
+    warn_exposed_tokens(&config);
+    unlock(&mut config)?;
+
+    // Commands touching local files wait for each other
+    let home_lock = match matches.subcommand_name() {
+        None | Some("oauth") | Some("authorize") => None,
+        Some(_) => Some(lock(&config)?),
+    };
 
     match matches.subcommand() {
-        ("oauth", _) => {
-            OAuth::request_authorization();
-        }
-        ("authorize", _) => {
-            OAuth::authorize();
-        }
+        ("oauth", _) => oauth(&config),
+        ("authorize", _) => authorize(&config),
         ("pick", Some(pick_matches)) => {
-            let quantity = pick_matches.value_of("quantity").unwrap();
+            if let Some(strategy) = pick_matches.value_of("strategy") {
+                config.set("strategy", strategy)?;
+            }
+            if pick_matches.is_present("diverse") {
+                let diversity = pick_matches.value_of("diverse").unwrap_or("domain");
+                config.set("diversity", diversity)?;
+            }
+            let filter = filter(pick_matches)?;
+            let output = Output::from(pick_matches);
+            let mut rng: Box<dyn RngCore> = match number(pick_matches, "seed")? {
+                Some(seed) => Box::new(StdRng::seed_from_u64(seed)),
+                None => Box::new(rand::thread_rng()),
+            };
 
-            match quantity.parse::<usize>() {
-                Ok(quantity) => {
-                    Library::pick(Some(quantity));
-                }
-                Err(_) => {
-                    logger::log("You must provide a valid quantity");
-                }
+            let articles = match pick_matches.value_of("budget") {
+                Some(budget) => pick_budget(
+                    &config,
+                    budget_minutes(budget)?,
+                    &filter,
+                    &mut *rng,
+                    &output,
+                )?,
+                None => pick(
+                    &config,
+                    number(pick_matches, "quantity")?.unwrap(),
+                    &filter,
+                    &mut *rng,
+                    &output,
+                )?,
             };
+
+            if output.interactive {
+                // Other commands may run while waiting for answers
+                drop(home_lock);
+                ask_snooze(&config, &articles)?;
+            }
+            Ok(())
+        }
+        ("renew", _) => renew(&config).and_then(|_| status(&config)),
+        ("status", _) => status(&config),
+        ("history", Some(history_matches)) => history(
+            &config,
+            day(history_matches, "from", false)?,
+            day(history_matches, "to", true)?,
+        ),
+        ("undo", Some(undo_matches)) => undo(&config, number(undo_matches, "count")?.unwrap_or(1)),
+        ("snooze", Some(snooze_matches)) => snooze(
+            &config,
+            snooze_matches.value_of("id").unwrap(),
+            until(snooze_matches.value_of("until").unwrap())?,
+        ),
+        ("favorite", Some(favorite_matches)) => {
+            let article = Library::favorite(&config, favorite_matches.value_of("id").unwrap())?;
+            logger::log(&format!("Marked \"{}\" as favorite", article.title));
+            Ok(())
+        }
+        ("tag", Some(tag_matches)) => {
+            let tags: Vec<String> = tag_matches
+                .values_of("tags")
+                .unwrap()
+                .map(str::to_owned)
+                .collect();
+            let article = Library::tag(&config, tag_matches.value_of("id").unwrap(), &tags)?;
+            logger::log(&format!(
+                "Tagged \"{}\" with {}",
+                article.title,
+                tags.join(", ")
+            ));
+            Ok(())
         }
-        ("renew", _) => {
-            Library::renew();
-            Library::status();
+        ("delete", Some(delete_matches)) => {
+            let article = Library::delete(&config, delete_matches.value_of("id").unwrap())?;
+            logger::log(&format!("Deleted \"{}\"", article.title));
+            Ok(())
         }
-        ("status", _) => {
-            Library::status();
+        ("add", Some(add_matches)) => {
+            let url = add_matches.value_of("url").unwrap();
+            Library::add(&config, url, add_matches.value_of("title"))?;
+            logger::log(&format!("{} will be saved on the next renew", url));
+            Ok(())
+        }
+        ("snapshots", Some(snapshots_matches)) => match snapshots_matches.subcommand() {
+            ("restore", Some(restore_matches)) => {
+                restore(&config, restore_matches.value_of("id").unwrap())
+            }
+            _ => snapshots(&config),
+        },
+        ("migrate", Some(migrate_matches)) => migrate(&config, migrate_matches.is_present("check")),
+        ("outbox", Some(outbox_matches)) => outbox(
+            &config,
+            outbox_matches.is_present("retry"),
+            outbox_matches.is_present("clear"),
+        ),
+        _ => Err(PickpocketError::InvalidArgument(
+            "Option not found".to_owned(),
+        )),
+    }
+}
+
+fn lock(config: &Configuration) -> Result<HomeLock> {
+    if let Some(lock) = HomeLock::try_acquire(config)? {
+        return Ok(lock);
+    }
+
+    logger::log_to_stderr(&format!(
+        "Another Pickpocket command is running (it holds {}), waiting for it to finish...",
+        HomeLock::path(config).display()
+    ));
+    HomeLock::acquire(config)
+}
+
+fn warn_exposed_tokens(config: &Configuration) {
+    for path in TokenHandler::with_configuration(config.clone()).exposed_tokens() {
+        logger::log_to_stderr(&format!(
+            "Warning: {} can be read by other users. Run chmod 600 on it",
+            path.display()
+        ));
+    }
+}
+
+/// Asks for the passphrase when files are, or are to be, encrypted and
+/// PICKPOCKET_PASSPHRASE is not set.
+fn unlock(config: &mut Configuration) -> Result<()> {
+    let encrypted = [
+        &config.library_file,
+        &config.oauth_token_file,
+        &config.authorization_token_file,
+        &config.history_file,
+        &config.outbox_file,
+    ]
+    .iter()
+    .any(|path| pickpocket::crypto::is_encrypted(path));
+    if config.passphrase.is_some() || !(config.encryption || encrypted) {
+        return Ok(());
+    }
+
+    // Without a terminal to ask on, reading encrypted files reports what's missing
+    if let Ok(passphrase) = rpassword::prompt_password("Pickpocket passphrase: ") {
+        config.set_passphrase(&passphrase)?;
+    }
+    Ok(())
+}
+
+fn configuration(matches: &ArgMatches) -> Result<Configuration> {
+    let mut config = Configuration::load(matches.value_of("config").map(Path::new))?;
+
+    for setting in matches.values_of("set").into_iter().flatten() {
+        match setting.split_once('=') {
+            Some((key, value)) => config.set(key.trim(), value.trim())?,
+            None => {
+                return Err(PickpocketError::InvalidArgument(format!(
+                    "Configuration overrides must look like key=value, got: {}",
+                    setting
+                )))
+            }
         }
-        _ => {
-            logger::log("Option not found");
+    }
+
+    Ok(config)
+}
+
+fn oauth(config: &Configuration) -> Result<()> {
+    let authorization_url = OAuth::request_authorization(config)?;
+    open::that(authorization_url).ok();
+    Ok(())
+}
+
+fn authorize(config: &Configuration) -> Result<()> {
+    OAuth::authorize(config)?;
+    logger::log("Pickpocket is now authorized");
+    Ok(())
+}
+
+fn number<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>> {
+    match matches.value_of(name).map(str::parse::<T>) {
+        Some(Ok(number)) => Ok(Some(number)),
+        Some(Err(_)) => Err(PickpocketError::InvalidArgument(format!(
+            "You must provide a valid {}",
+            name.replace('-', " ")
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn filter(matches: &ArgMatches) -> Result<Filter> {
+    let values = |name| {
+        matches
+            .values_of(name)
+            .into_iter()
+            .flatten()
+            .map(str::to_owned)
+            .collect()
+    };
+    let title_matches = match matches.value_of("title-matches").map(Regex::new) {
+        Some(Ok(pattern)) => Some(pattern),
+        Some(Err(error)) => {
+            return Err(PickpocketError::InvalidArgument(format!(
+                "Invalid title pattern: {}",
+                error
+            )))
         }
+        None => None,
+    };
+
+    let filter = Filter {
+        tags: values("tag"),
+        domains: values("domain"),
+        min_words: number(matches, "min-words")?,
+        max_words: number(matches, "max-words")?,
+        max_minutes: number(matches, "max-minutes")?,
+        title_matches,
     };
+
+    if let (Some(min), Some(max)) = (filter.min_words, filter.max_words) {
+        if min > max {
+            return Err(PickpocketError::InvalidArgument(
+                "--min-words must not be greater than --max-words".to_owned(),
+            ));
+        }
+    }
+
+    Ok(filter)
+}
+
+/// What `pick` does with the articles it chooses.
+struct Output<'a> {
+    dry_run: bool,
+    open: bool,
+    print: Option<&'a str>,
+    /// Offers to snooze picked articles.
+    interactive: bool,
+}
+
+impl<'a> Output<'a> {
+    fn from(matches: &'a ArgMatches) -> Self {
+        let dry_run = matches.is_present("dry-run");
+
+        Output {
+            dry_run,
+            open: !dry_run && !matches.is_present("no-open"),
+            print: matches.value_of("print"),
+            interactive: matches.is_present("interactive"),
+        }
+    }
+
+    fn opening(&self) -> Opening {
+        match (self.print, self.open) {
+            (Some(_), _) => Opening::Print,
+            (None, true) => Opening::Browser,
+            (None, false) => Opening::NotOpened,
+        }
+    }
+
+    fn show(&self, articles: &[Article]) -> Result<()> {
+        for article in articles {
+            match self.print {
+                Some("url") => println!("{}", article.url),
+                Some("title") => println!("{}", article.title),
+                Some(_) => println!("{}", serde_json::to_string(article)?),
+                None if self.dry_run => {
+                    self.log(&format!(
+                        "Would pick \"{}\" ({})",
+                        article.title, article.url
+                    ));
+                }
+                None => {}
+            }
+
+            if self.open {
+                open::that(&article.url).ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logs on stderr while printing articles, keeping stdout clean for pipes.
+    fn log(&self, message: &str) {
+        match self.print {
+            Some(_) => logger::log_to_stderr(message),
+            None => logger::log(message),
+        };
+    }
+}
+
+fn pick(
+    config: &Configuration,
+    quantity: usize,
+    filter: &Filter,
+    rng: &mut dyn RngCore,
+    output: &Output,
+) -> Result<Vec<Article>> {
+    let articles = if output.dry_run {
+        Library::draw_with_rng(config, Some(quantity), filter, rng)?
+    } else {
+        Library::pick_with_rng(config, Some(quantity), filter, output.opening(), rng)?
+    };
+
+    output.show(&articles)?;
+
+    if articles.is_empty() && !filter.is_empty() {
+        output.log("No unread articles match your filters, nothing was marked as read");
+    } else if articles.len() < quantity && !filter.is_empty() {
+        output.log(&format!(
+            "Only {} unread articles match your filters",
+            articles.len()
+        ));
+    } else if articles.len() < quantity {
+        output.log("You have read all articles!");
+    }
+
+    Ok(articles)
+}
+
+fn pick_budget(
+    config: &Configuration,
+    minutes: u64,
+    filter: &Filter,
+    rng: &mut dyn RngCore,
+    output: &Output,
+) -> Result<Vec<Article>> {
+    let articles = if output.dry_run {
+        Library::draw_budget_with_rng(config, minutes, filter, rng)?
+    } else {
+        Library::pick_budget_with_rng(config, minutes, filter, output.opening(), rng)?
+    };
+
+    if articles.is_empty() {
+        output.log(&format!(
+            "No unread articles fit in {} minutes, nothing was marked as read",
+            minutes
+        ));
+        return Ok(articles);
+    }
+
+    output.show(&articles)?;
+
+    let total: u64 = articles
+        .iter()
+        .filter_map(|article| article.reading_minutes(config.words_per_minute))
+        .sum();
+    output.log(&format!(
+        "{} {} articles, about {} of your {} minutes",
+        if output.dry_run {
+            "Would pick"
+        } else {
+            "Picked"
+        },
+        articles.len(),
+        total,
+        minutes
+    ));
+
+    Ok(articles)
+}
+
+/// Parses a reading time like `25m`, `1h`, `1h30m` or `25` (minutes).
+fn budget_minutes(budget: &str) -> Result<u64> {
+    match minutes(budget, 1) {
+        Some(minutes) if minutes > 0 => Ok(minutes),
+        _ => Err(PickpocketError::InvalidArgument(format!(
+            "You must provide a valid budget (like 25m or 1h30m), got: {}",
+            budget
+        ))),
+    }
+}
+
+/// Parses a duration like `2w`, `3d`, `1h30m` into minutes. Bare numbers
+/// count as `unit` minutes.
+fn minutes(duration: &str, unit: u64) -> Option<u64> {
+    let mut minutes = 0;
+    let mut digits = String::new();
+
+    for character in duration.trim().chars() {
+        let multiplier = match character {
+            '0'..='9' => {
+                digits.push(character);
+                continue;
+            }
+            'w' => 7 * 24 * 60,
+            'd' => 24 * 60,
+            'h' => 60,
+            'm' => 1,
+            _ => return None,
+        };
+
+        minutes += digits.parse::<u64>().ok()?.checked_mul(multiplier)?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() {
+        minutes += digits.parse::<u64>().ok()?.checked_mul(unit)?;
+    }
+
+    Some(minutes)
+}
+
+/// Parses a `YYYY-MM-DD` day into the Unix timestamp of its first (or, with
+/// `end`, last) second, in local time.
+fn day_timestamp(value: &str, end: bool) -> Option<i64> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|day| match end {
+            false => day.and_hms_opt(0, 0, 0),
+            true => day.and_hms_opt(23, 59, 59),
+        })
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map(|time| time.timestamp())
+}
+
+/// Parses a `YYYY-MM-DD` day argument, see [`day_timestamp`].
+fn day(matches: &ArgMatches, name: &str, end: bool) -> Result<Option<i64>> {
+    let value = match matches.value_of(name) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    match day_timestamp(value, end) {
+        Some(time) => Ok(Some(time)),
+        None => Err(PickpocketError::InvalidArgument(format!(
+            "You must provide a valid --{} day (YYYY-MM-DD), got: {}",
+            name, value
+        ))),
+    }
+}
+
+/// Parses a snooze date (`YYYY-MM-DD`) or duration from now (`3d`, `2w`, or
+/// bare days) into a Unix timestamp.
+fn until(value: &str) -> Result<i64> {
+    let from_now = minutes(value, 24 * 60)
+        .filter(|minutes| *minutes > 0)
+        .map(|minutes| Local::now().timestamp() + minutes as i64 * 60);
+
+    let until = day_timestamp(value, false).or(from_now).ok_or_else(|| {
+        PickpocketError::InvalidArgument(format!(
+            "You must provide a valid date (YYYY-MM-DD) or duration (like 3d or 2w), got: {}",
+            value
+        ))
+    })?;
+
+    if until <= Local::now().timestamp() {
+        return Err(PickpocketError::InvalidArgument(format!(
+            "You must snooze until a future date, got: {}",
+            value
+        )));
+    }
+    Ok(until)
+}
+
+fn snooze(config: &Configuration, article_id: &str, until: i64) -> Result<()> {
+    let article = Library::snooze(config, article_id, until)?;
+    let date = Local
+        .timestamp_opt(until, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+
+    logger::log(&format!("Snoozed \"{}\" until {}", article.title, date));
+    Ok(())
+}
+
+/// Offers to snooze each picked article, reading answers from stdin. Call it
+/// without holding the home lock: it is only taken to snooze each answer.
+fn ask_snooze(config: &Configuration, articles: &[Article]) -> Result<()> {
+    for article in articles {
+        logger::log(&format!(
+            "Snooze \"{}\"? Enter a date or duration (like 3d or 2024-06-01), or press Enter to keep it",
+            article.title
+        ));
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim();
+
+        if !answer.is_empty() {
+            let until = until(answer)?;
+            let _lock = lock(config)?;
+            snooze(config, &article.id, until)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn history(config: &Configuration, from: Option<i64>, to: Option<i64>) -> Result<()> {
+    let history = History::load(config)?;
+    let picks = history.between(from, to);
+
+    for pick in &picks {
+        let time = Local
+            .timestamp_opt(pick.time, 0)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let undone = if pick.undone.is_some() {
+            " (undone)"
+        } else if pick.archived.is_some() {
+            " (archived)"
+        } else {
+            ""
+        };
+
+        logger::log(&format!(
+            "{} [{}] {} - {} ({}){}",
+            time, pick.opening, pick.article.id, pick.article.title, pick.article.url, undone
+        ));
+    }
+
+    logger::log(&format!("{} picks", picks.len()));
+    Ok(())
+}
+
+fn undo(config: &Configuration, count: usize) -> Result<()> {
+    let undo = Library::undo(config, count)?;
+
+    for article in &undo.restored {
+        logger::log(&format!("Moved \"{}\" back to unread", article.title));
+    }
+    for article in &undo.archived {
+        logger::log(&format!(
+            "Could not undo \"{}\", it was already archived on Pocket",
+            article.title
+        ));
+    }
+    if undo.restored.is_empty() && undo.archived.is_empty() {
+        logger::log("There are no picks to undo");
+    }
+
+    Ok(())
+}
+
+fn renew(config: &Configuration) -> Result<()> {
+    let summary = Library::renew(config)?;
+    logger::log(&format!(
+        "Successfully archived {} articles",
+        summary.archived
+    ));
+    if summary.pending > 0 {
+        logger::log(&format!(
+            "Could not archive {} articles, they will be archived on the next renew",
+            summary.pending
+        ));
+    }
+    if summary.failed > 0 {
+        logger::log(&format!(
+            "Pocket kept rejecting {} changes, see `pickpocket outbox`",
+            summary.failed
+        ));
+    }
+    logger::log(&format!("Total articles retrieved: {}", summary.retrieved));
+    logger::log(&format!(
+        "Removed {} articles archived or deleted on Pocket",
+        summary.removed
+    ));
+    logger::log("Refreshed library");
+    Ok(())
+}
+
+fn snapshots(config: &Configuration) -> Result<()> {
+    let snapshots = Snapshot::list(config)?;
+
+    for snapshot in &snapshots {
+        let time = Local
+            .timestamp_opt(snapshot.time, 0)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+
+        logger::log(&format!(
+            "{} - {}, before {}",
+            snapshot.id, time, snapshot.operation
+        ));
+    }
+
+    logger::log(&format!("{} snapshots", snapshots.len()));
+    Ok(())
+}
+
+fn restore(config: &Configuration, id: &str) -> Result<()> {
+    let snapshot = Snapshot::restore(config, id)?;
+    logger::log(&format!(
+        "Restored the library as it was before {} (snapshot {})",
+        snapshot.operation, snapshot.id
+    ));
+    status(config)
+}
+
+fn migrate(config: &Configuration, check: bool) -> Result<()> {
+    let report = Library::migrate(config, check)?;
+
+    if !report.is_needed() {
+        logger::log(&format!(
+            "Library is up to date (schema version {})",
+            report.to
+        ));
+        return Ok(());
+    }
+
+    match &report.backup {
+        Some(backup) => logger::log(&format!(
+            "Upgraded library from schema version {} to {} (backup at {})",
+            report.from,
+            report.to,
+            backup.display()
+        )),
+        None => logger::log(&format!(
+            "Library would be upgraded from schema version {} to {}",
+            report.from, report.to
+        )),
+    };
+    for change in &report.changes {
+        logger::log(&format!("  {}", change));
+    }
+
+    Ok(())
+}
+
+fn outbox(config: &Configuration, retry: bool, clear: bool) -> Result<()> {
+    let mut outbox = Outbox::load(config)?;
+
+    if retry {
+        let count = outbox.retry_failed();
+        outbox.save(config)?;
+        logger::log(&format!("Queued {} failed changes again", count));
+    } else if clear {
+        let count = outbox.clear_failed();
+        outbox.save(config)?;
+        logger::log(&format!("Forgot {} failed changes", count));
+    }
+
+    logger::log(&format!("{} changes waiting", outbox.pending().len()));
+    for entry in outbox.pending() {
+        logger::log(&format!("  {:?}", entry.action));
+    }
+
+    if !outbox.failed().is_empty() {
+        logger::log(&format!("{} changes failed", outbox.failed().len()));
+        for entry in outbox.failed() {
+            logger::log(&format!(
+                "  {:?} ({})",
+                entry.action,
+                entry.last_error.as_deref().unwrap_or("unknown error")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn status(config: &Configuration) -> Result<()> {
+    let status = Library::status(config)?;
+    logger::log(&format!("You have {} read articles", status.read));
+    logger::log(&format!("You have {} unread articles", status.unread));
+    if status.snoozed > 0 {
+        logger::log(&format!("You have {} snoozed articles", status.snoozed));
+    }
+    Ok(())
 }
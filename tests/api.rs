@@ -0,0 +1,213 @@
+mod support;
+
+use pickpocket::{Article, PickpocketError, API};
+use std::time::Duration;
+use support::{Failure, MockPocket};
+
+fn article(id: &str) -> Article {
+    Article {
+        id: id.to_owned(),
+        url: format!("https://example.com/articles/{}", id),
+        title: format!("Article {}", id),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn retrieves_every_page() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(7);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+    config.max_concurrent_requests = 2;
+
+    let response = API::with_configuration(config).retrieve().unwrap();
+
+    assert_eq!(7, response.list.len());
+    assert_eq!(
+        Some("https://example.com/articles/7"),
+        response.list["7"].url()
+    );
+    assert_eq!("Article 7", response.list["7"].title());
+    assert_eq!(Some(1000), response.list["7"].word_count);
+
+    let mut offsets: Vec<usize> = pocket
+        .requests_to("/v3/get")
+        .into_iter()
+        .map(|request| request.form["offset"].parse().unwrap())
+        .collect();
+    offsets.sort();
+    assert_eq!(vec![0, 2, 4, 6, 8], offsets);
+}
+
+#[test]
+fn stops_when_the_server_ignores_the_offset() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(6);
+    pocket.ignore_offset();
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+    config.max_concurrent_requests = 2;
+
+    let response = API::with_configuration(config).retrieve().unwrap();
+
+    assert_eq!(2, response.list.len());
+    assert_eq!(3, pocket.requests_to("/v3/get").len());
+}
+
+#[test]
+fn retrieves_full_pages_of_the_size_pocket_sends() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(65);
+    pocket.cap_count(30);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.set("page_size", "30").unwrap();
+
+    let response = API::with_configuration(config.clone()).retrieve().unwrap();
+    assert_eq!(65, response.list.len());
+
+    // Pocket would cut larger pages short, which reads like the last page
+    config.page_size = 50;
+    match API::with_configuration(config).retrieve() {
+        Err(PickpocketError::InvalidConfiguration(_)) => {}
+        other => panic!("Expected an invalid configuration, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn retrieves_asynchronously_waiting_for_slow_pages() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(9);
+    pocket.delay_page(3, Duration::from_millis(300));
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 3;
+
+    let response = API::with_configuration(config)
+        .retrieve_async()
+        .await
+        .unwrap();
+
+    assert_eq!(9, response.list.len());
+}
+
+#[test]
+fn returns_the_first_page_when_everything_fits_in_it() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    let response = API::with_configuration(config).retrieve().unwrap();
+
+    assert_eq!(3, response.list.len());
+    assert_eq!(1, pocket.requests_to("/v3/get").len());
+}
+
+#[test]
+fn tolerates_items_without_urls_and_empty_lists() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_item("1", serde_json::json!({ "item_id": "1", "status": "0" }));
+
+    let config = pocket.authorized_configuration(home.path());
+    let response = API::with_configuration(config.clone()).retrieve().unwrap();
+    assert_eq!(None, response.list["1"].url());
+
+    let summary = pickpocket::Library::renew(&config).unwrap();
+    assert_eq!(0, summary.retrieved);
+}
+
+#[test]
+fn fails_on_malformed_json() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(6);
+    pocket.fail_page(2, Failure::MalformedJson);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+
+    match API::with_configuration(config).retrieve() {
+        Err(PickpocketError::Json(_)) => {}
+        other => panic!("Expected a JSON error, got {:?}", other),
+    }
+}
+
+#[test]
+fn fails_with_pocket_error_responses() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(6);
+    pocket.fail_page(4, Failure::Status(503, "Pocket is down."));
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+
+    match API::with_configuration(config).retrieve() {
+        Err(PickpocketError::Pocket { status, message }) => {
+            assert_eq!(503, status);
+            assert_eq!("Pocket is down.", message);
+        }
+        other => panic!("Expected a Pocket error, got {:?}", other),
+    }
+}
+
+#[test]
+fn fails_without_an_access_token() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+
+    let config = pocket.configuration(home.path());
+
+    match API::with_configuration(config).retrieve() {
+        Err(PickpocketError::MissingToken(_)) => {}
+        other => panic!("Expected a missing token error, got {:?}", other),
+    }
+    assert!(pocket.requests().is_empty());
+}
+
+#[tokio::test]
+async fn archives_articles() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    let (first, second) = (article("1"), article("2"));
+
+    let archived = API::with_configuration(config)
+        .archive_async(vec![&first, &second])
+        .await
+        .unwrap();
+
+    assert_eq!(vec!["1", "2"], archived);
+
+    let actions = pocket.actions();
+    assert_eq!(2, actions.len());
+    assert_eq!("archive", actions[0]["action"]);
+    assert_eq!("1", actions[0]["item_id"]);
+    assert_eq!("2", actions[1]["item_id"]);
+}
+
+#[test]
+fn fails_to_archive_when_pocket_rejects_the_request() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.fail("/v3/send", Failure::Status(400, "Invalid request."));
+
+    let config = pocket.authorized_configuration(home.path());
+    let first = article("1");
+
+    match API::with_configuration(config).archive(vec![&first]) {
+        Err(PickpocketError::Pocket { status, .. }) => assert_eq!(400, status),
+        other => panic!("Expected a Pocket error, got {:?}", other),
+    }
+}
@@ -0,0 +1,838 @@
+mod support;
+
+use pickpocket::{
+    Filter, History, Library, Opening, Outbox, PickpocketError, Snapshot, SCHEMA_VERSION,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::json;
+use std::time::Duration;
+use support::{Failure, MockPocket};
+
+#[test]
+fn renews_the_library_end_to_end() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(5);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+
+    // First sync: everything is unread
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!(5, summary.retrieved);
+
+    let picked = Library::pick(&config, Some(2)).unwrap();
+    let status = Library::status(&config).unwrap();
+    assert_eq!((2, 3), (status.read, status.unread));
+
+    // Second sync: picked articles are archived on Pocket and dropped locally
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!(2, summary.archived);
+    assert_eq!(0, summary.retrieved);
+
+    let mut archived: Vec<String> = pocket
+        .actions()
+        .iter()
+        .map(|action| action["item_id"].as_str().unwrap().to_owned())
+        .collect();
+    let mut expected: Vec<String> = picked.into_iter().map(|article| article.id).collect();
+    archived.sort();
+    expected.sort();
+    assert_eq!(expected, archived);
+
+    let library = Library::load(&config).unwrap();
+    assert!(library.read().articles.is_empty());
+    assert_eq!(3, library.unread().articles.len());
+
+    // Reading metadata is kept on the library file
+    let article = library.unread().articles.values().next().unwrap();
+    assert_eq!(Some(1000), article.word_count);
+    assert_eq!(Some(5), article.reading_time);
+    assert_eq!(Some("example.com".to_owned()), article.domain);
+    assert_eq!(vec!["rust"], article.tags);
+    assert_eq!(vec!["Jane Doe"], article.authors);
+    assert!(article.is_article && article.has_image);
+}
+
+#[test]
+fn keeps_the_library_when_retrieval_fails() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    pocket.fail("/v3/get", Failure::Status(503, "Pocket is down."));
+
+    match Library::renew(&config) {
+        Err(PickpocketError::Pocket { status, .. }) => assert_eq!(503, status),
+        other => panic!("Expected a Pocket error, got {:?}", other),
+    }
+    assert_eq!(3, Library::status(&config).unwrap().unread);
+}
+
+#[test]
+fn keeps_library_changes_made_while_renewing() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+    pocket.delay_page(0, Duration::from_millis(500));
+
+    let renewing = {
+        let config = config.clone();
+        std::thread::spawn(move || Library::renew(&config))
+    };
+    std::thread::sleep(Duration::from_millis(100));
+    Library::favorite(&config, "1").unwrap();
+    renewing.join().unwrap().unwrap();
+
+    assert!(Library::load(&config).unwrap().unread().articles["1"].favorite);
+}
+
+#[test]
+fn reports_a_corrupt_library_file() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    let config = pocket.configuration(home.path());
+
+    std::fs::write(&config.library_file, "read: [this is not a library").unwrap();
+
+    match Library::load(&config) {
+        Err(error @ PickpocketError::CorruptLibrary(_)) => assert_eq!(4, error.exit_code()),
+        other => panic!("Expected a corrupt library, got {:?}", other),
+    }
+
+    // Whatever the storage
+    let mut config = config;
+    config.set("storage", "json").unwrap();
+    std::fs::write(&config.library_file, "{\"read\": [").unwrap();
+    match Library::load(&config) {
+        Err(error @ PickpocketError::CorruptLibrary(_)) => assert_eq!(4, error.exit_code()),
+        other => panic!("Expected a corrupt library, got {:?}", other),
+    }
+}
+
+#[test]
+fn syncs_only_what_changed_since_the_last_renew() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(4);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+
+    Library::renew(&config).unwrap();
+    let since = Library::load(&config).unwrap().since();
+    assert!(since.is_some());
+
+    // Changes made on Pocket from another device
+    pocket.set_status("1", "1");
+    pocket.set_status("2", "2");
+    pocket.add_item("5", MockPocket::item("5"));
+
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!((1, 2), (summary.retrieved, summary.removed));
+
+    let delta = pocket.requests_to("/v3/get").pop().unwrap();
+    assert_eq!("all", delta.form["state"]);
+    assert_eq!(since.unwrap().to_string(), delta.form["since"]);
+
+    let library = Library::load(&config).unwrap();
+    let mut ids: Vec<&String> = library.unread().articles.keys().collect();
+    ids.sort();
+    assert_eq!(vec!["3", "4", "5"], ids);
+    assert!(library.since() > since);
+}
+
+#[test]
+fn retrieves_libraries_larger_than_fifty_pages() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(120);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.page_size = 2;
+
+    assert_eq!(120, Library::renew(&config).unwrap().retrieved);
+}
+
+#[test]
+fn only_drops_read_articles_pocket_archived() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+    pocket.reject_actions_on("2");
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+    Library::pick(&config, Some(3)).unwrap();
+
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!((2, 1), (summary.archived, summary.pending));
+
+    let library = Library::load(&config).unwrap();
+    let read: Vec<&String> = library.read().articles.keys().collect();
+    assert_eq!(vec!["2"], read);
+    assert!(library.unread().articles.is_empty());
+}
+
+#[test]
+fn keeps_read_articles_when_archiving_fails() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(2);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+    Library::pick(&config, Some(1)).unwrap();
+
+    pocket.fail("/v3/send", Failure::Status(503, "Pocket is down."));
+
+    assert!(Library::renew(&config).is_err());
+    let status = Library::status(&config).unwrap();
+    assert_eq!((1, 1), (status.read, status.unread));
+}
+
+#[test]
+fn keeps_unread_articles_when_a_full_sync_comes_back_empty() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+
+    let config = pocket.authorized_configuration(home.path());
+    std::fs::write(
+        &config.library_file,
+        "read:\n  articles: {}\nunread:\n  articles:\n    \"9\":\n      id: \"9\"\n      url: https://example.com\n      title: Local\n",
+    )
+    .unwrap();
+
+    Library::renew(&config).unwrap();
+
+    let library = Library::load(&config).unwrap();
+    assert_eq!(1, library.unread().articles.len());
+    assert_eq!(None, library.since());
+}
+
+#[test]
+fn sends_offline_changes_on_renew() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    Library::favorite(&config, "1").unwrap();
+    Library::tag(&config, "2", &["rust".to_owned()]).unwrap();
+    Library::delete(&config, "3").unwrap();
+    Library::add(&config, "https://example.com/new", Some("New")).unwrap();
+    assert!(Library::favorite(&config, "404").is_err());
+
+    // Nothing reaches Pocket until the next renew
+    assert!(pocket.actions().is_empty());
+    assert_eq!(4, Outbox::load(&config).unwrap().pending().len());
+
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!((4, 0), (summary.sent, summary.failed));
+
+    let actions = pocket.actions();
+    let names: Vec<&str> = actions
+        .iter()
+        .map(|action| action["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["favorite", "tags_add", "delete", "add"], names);
+    assert_eq!("rust", actions[1]["tags"]);
+    assert!(Outbox::load(&config).unwrap().pending().is_empty());
+
+    let library = Library::load(&config).unwrap();
+    assert!(library.unread().articles["1"].favorite);
+    assert_eq!(vec!["rust"], library.unread().articles["2"].tags);
+    assert!(!library.unread().articles.contains_key("3"));
+}
+
+#[test]
+fn gives_up_on_actions_pocket_keeps_rejecting() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(2);
+    pocket.reject_actions_on("1");
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.max_attempts = 2;
+    Library::renew(&config).unwrap();
+    Library::favorite(&config, "1").unwrap();
+    Library::favorite(&config, "2").unwrap();
+
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!((1, 0), (summary.sent, summary.failed));
+    assert_eq!(1, Outbox::load(&config).unwrap().pending()[0].attempts);
+
+    let summary = Library::renew(&config).unwrap();
+    assert_eq!((0, 1), (summary.sent, summary.failed));
+
+    let mut outbox = Outbox::load(&config).unwrap();
+    assert!(outbox.pending().is_empty());
+    assert_eq!(1, outbox.failed().len());
+
+    // Failed actions can be queued again
+    assert_eq!(1, outbox.retry_failed());
+    assert_eq!(0, outbox.pending()[0].attempts);
+}
+
+#[test]
+fn picks_the_same_articles_with_the_same_seed() {
+    let pocket = MockPocket::start();
+    pocket.add_items(20);
+
+    let picks: Vec<Vec<String>> = (0..2)
+        .map(|_| {
+            let home = tempfile::tempdir().unwrap();
+            let config = pocket.authorized_configuration(home.path());
+            Library::renew(&config).unwrap();
+
+            Library::pick_with_rng(
+                &config,
+                Some(3),
+                &Filter::new(),
+                Opening::Library,
+                &mut StdRng::seed_from_u64(42),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|article| article.id)
+            .collect()
+        })
+        .collect();
+
+    assert_eq!(3, picks[0].len());
+    assert_eq!(picks[0], picks[1]);
+}
+
+#[test]
+fn only_picks_articles_matching_the_filter() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+    let mut short = MockPocket::item("4");
+    short["word_count"] = json!("100");
+    short["tags"] = json!({ "news": { "item_id": "4", "tag": "news" } });
+    pocket.add_item("4", short);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    let filter = Filter {
+        tags: vec!["news".to_owned()],
+        max_minutes: Some(1),
+        ..Default::default()
+    };
+    let picked = Library::pick_with_rng(
+        &config,
+        Some(2),
+        &filter,
+        Opening::Library,
+        &mut rand::thread_rng(),
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["4"],
+        picked.iter().map(|a| a.id.as_str()).collect::<Vec<_>>()
+    );
+
+    // Nothing left matching: nothing is marked as read
+    let picked = Library::pick_with_rng(
+        &config,
+        Some(1),
+        &filter,
+        Opening::Library,
+        &mut rand::thread_rng(),
+    )
+    .unwrap();
+    assert!(picked.is_empty());
+    let status = Library::status(&config).unwrap();
+    assert_eq!((1, 3), (status.read, status.unread));
+    assert_eq!(1, Outbox::load(&config).unwrap().pending().len());
+}
+
+#[test]
+fn picks_articles_fitting_a_reading_budget() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+    let mut short = MockPocket::item("4");
+    short["word_count"] = json!("400");
+    pocket.add_item("4", short);
+    let mut unknown = MockPocket::item("5");
+    unknown["word_count"] = json!("");
+    pocket.add_item("5", unknown);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    // 5 + 5 + 2 minutes at 200 words per minute
+    let mut picked: Vec<String> = Library::pick_budget(&config, 12)
+        .unwrap()
+        .into_iter()
+        .map(|article| article.id)
+        .collect();
+    picked.sort();
+    assert_eq!(3, picked.len());
+    assert!(picked.contains(&"4".to_owned()));
+
+    let status = Library::status(&config).unwrap();
+    assert_eq!((3, 2), (status.read, status.unread));
+
+    // The remaining 5 minutes article does not fit, the other has no length
+    assert!(Library::pick_budget(&config, 4).unwrap().is_empty());
+}
+
+#[test]
+fn draws_what_pick_would_pick_without_changing_the_library() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(10);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    let filter = Filter::new();
+    let drawn =
+        Library::draw_with_rng(&config, Some(3), &filter, &mut StdRng::seed_from_u64(7)).unwrap();
+    let status = Library::status(&config).unwrap();
+    assert_eq!((0, 10), (status.read, status.unread));
+    assert!(Outbox::load(&config).unwrap().pending().is_empty());
+
+    let picked = Library::pick_with_rng(
+        &config,
+        Some(3),
+        &filter,
+        Opening::Library,
+        &mut StdRng::seed_from_u64(7),
+    )
+    .unwrap();
+    assert_eq!(drawn, picked);
+}
+
+#[test]
+fn undoes_picks_not_archived_yet() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(4);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    let archived = Library::pick(&config, Some(1)).unwrap();
+    Library::renew(&config).unwrap();
+    let picked = Library::pick(&config, Some(2)).unwrap();
+
+    let history = History::load(&config).unwrap();
+    assert_eq!(3, history.picks().len());
+    assert_eq!(Opening::Library, history.picks()[0].opening);
+    assert_eq!(archived[0], history.picks()[0].article);
+
+    // The last two picks go back to unread, the archived one stays archived
+    let undo = Library::undo(&config, 3).unwrap();
+    assert_eq!(2, undo.restored.len());
+    assert_eq!(archived, undo.archived);
+    assert!(picked.iter().all(|article| undo.restored.contains(article)));
+
+    let status = Library::status(&config).unwrap();
+    assert_eq!((0, 3), (status.read, status.unread));
+    assert!(Outbox::load(&config).unwrap().pending().is_empty());
+
+    let history = History::load(&config).unwrap();
+    assert_eq!(
+        2,
+        history
+            .picks()
+            .iter()
+            .filter(|pick| pick.undone.is_some())
+            .count()
+    );
+
+    // The archived pick was reported once, and does not use up later undos
+    Library::pick(&config, Some(1)).unwrap();
+    let undo = Library::undo(&config, 1).unwrap();
+    assert_eq!(1, undo.restored.len());
+    assert!(undo.archived.is_empty());
+    assert!(Library::undo(&config, 1).unwrap().archived.is_empty());
+}
+
+#[test]
+fn spreads_diverse_picks_across_domains() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    for id in 1..=9 {
+        let id = id.to_string();
+        let mut item = MockPocket::item(&id);
+        let site = if id == "9" {
+            "other.org"
+        } else {
+            "example.com"
+        };
+        item["given_url"] = json!(format!("https://{}/{}", site, id));
+        pocket.add_item(&id, item);
+    }
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.set("diversity", "domain").unwrap();
+    Library::renew(&config).unwrap();
+
+    for seed in 0..5 {
+        let drawn = Library::draw_with_rng(
+            &config,
+            Some(3),
+            &Filter::new(),
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap();
+
+        // Both sites first, then back to the only site left
+        assert_eq!(3, drawn.len());
+        assert!(drawn[..2].iter().any(|article| article.id == "9"));
+    }
+}
+
+#[test]
+fn ignores_snoozed_articles_until_their_date() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+
+    let later = 4102444800; // 2100-01-01
+    Library::snooze(&config, "1", later).unwrap();
+
+    // A picked article can be snoozed too, cancelling its archiving
+    let picked = Library::pick(&config, Some(2)).unwrap();
+    assert!(picked.iter().all(|article| article.id != "1"));
+    Library::snooze(&config, &picked[0].id, later).unwrap();
+    assert_eq!(1, Outbox::load(&config).unwrap().pending().len());
+
+    // Snoozed articles survive renew
+    pocket.add_item("4", MockPocket::item("4"));
+    Library::renew(&config).unwrap();
+    let status = Library::status(&config).unwrap();
+    assert_eq!((0, 1, 2), (status.read, status.unread, status.snoozed));
+
+    // And are back to unread once the date passes
+    Library::snooze(&config, "1", 0).unwrap();
+    let library = Library::load(&config).unwrap();
+    assert_eq!(None, library.unread().articles["1"].snoozed_until);
+    assert_eq!(1, library.snoozed().articles.len());
+}
+
+#[test]
+fn keeps_the_shuffle_bag_order_across_runs() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(6);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.set("strategy", "shuffle").unwrap();
+    Library::renew(&config).unwrap();
+
+    let draw = |seed: u64| -> Vec<String> {
+        Library::draw_with_rng(
+            &config,
+            Some(8),
+            &Filter::new(),
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|article| article.id)
+        .collect()
+    };
+
+    // Picking fills the bag, later runs follow it whatever the seed
+    let picked = Library::pick_with_rng(
+        &config,
+        Some(1),
+        &Filter::new(),
+        Opening::Library,
+        &mut StdRng::seed_from_u64(0),
+    )
+    .unwrap();
+    let bag = draw(1);
+    assert_eq!(5, bag.len());
+    assert!(!bag.contains(&picked[0].id));
+    assert_eq!(bag, draw(2));
+
+    // Synced articles join the bag without reshuffling it
+    pocket.add_item("7", MockPocket::item("7"));
+    pocket.add_item("8", MockPocket::item("8"));
+    Library::renew(&config).unwrap();
+    Library::pick_with_rng(
+        &config,
+        Some(1),
+        &Filter::new(),
+        Opening::Library,
+        &mut StdRng::seed_from_u64(3),
+    )
+    .unwrap();
+
+    let refilled = draw(4);
+    assert_eq!(6, refilled.len());
+    assert!(refilled.contains(&"7".to_owned()) && refilled.contains(&"8".to_owned()));
+    let kept: Vec<&String> = refilled.iter().filter(|id| bag.contains(id)).collect();
+    let expected: Vec<&String> = bag.iter().filter(|id| refilled.contains(id)).collect();
+    assert_eq!(expected, kept);
+    assert_eq!(refilled, draw(5));
+}
+
+#[test]
+fn shuffles_the_bag_with_the_seed() {
+    let bag = |seed: u64| -> Vec<String> {
+        let home = tempfile::tempdir().unwrap();
+        let pocket = MockPocket::start();
+        pocket.add_items(12);
+
+        let mut config = pocket.authorized_configuration(home.path());
+        config.set("strategy", "shuffle").unwrap();
+        Library::renew(&config).unwrap();
+
+        Library::draw_with_rng(
+            &config,
+            Some(12),
+            &Filter::new(),
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|article| article.id)
+        .collect()
+    };
+
+    let first = bag(0);
+    let mut sorted = first.clone();
+    sorted.sort();
+    assert_ne!(sorted, first);
+    assert_ne!(first, bag(1));
+}
+
+#[test]
+fn keeps_the_library_on_the_configured_store() {
+    for storage in &["json", "memory"] {
+        let home = tempfile::tempdir().unwrap();
+        let pocket = MockPocket::start();
+        pocket.add_items(3);
+
+        let mut config = pocket.authorized_configuration(home.path());
+        config.set("storage", storage).unwrap();
+        Library::renew(&config).unwrap();
+        Library::pick(&config, Some(1)).unwrap();
+
+        let status = Library::status(&config).unwrap();
+        assert_eq!((1, 2), (status.read, status.unread));
+
+        let on_disk = std::fs::read_to_string(&config.library_file).ok();
+        match *storage {
+            "json" => assert!(serde_json::from_str::<serde_json::Value>(&on_disk.unwrap()).is_ok()),
+            _ => assert_eq!(None, on_disk),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn keeps_library_and_history_on_sqlite() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.set("storage", "sqlite").unwrap();
+    Library::renew(&config).unwrap();
+    let picked = Library::pick(&config, Some(1)).unwrap();
+
+    let status = Library::status(&config).unwrap();
+    assert_eq!((1, 2), (status.read, status.unread));
+    assert!(config.database_file.exists());
+    assert!(!config.library_file.exists());
+    assert!(!config.history_file.exists());
+
+    let history = History::load(&config).unwrap();
+    assert_eq!(picked[0], history.picks()[0].article);
+}
+
+#[test]
+fn upgrades_unversioned_libraries_keeping_a_backup() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    let config = pocket.authorized_configuration(home.path());
+
+    // Written by the first release, before libraries were versioned
+    let old = include_str!("fixtures/baseline_library_file");
+    std::fs::write(&config.library_file, old).unwrap();
+
+    let check = Library::migrate(&config, true).unwrap();
+    assert_eq!((1, SCHEMA_VERSION), (check.from, check.to));
+    assert_eq!(2, check.changes.len());
+    assert_eq!(None, check.backup);
+    assert_eq!(old, std::fs::read_to_string(&config.library_file).unwrap());
+
+    // Loading upgrades the file on its own
+    let status = Library::status(&config).unwrap();
+    assert_eq!((1, 1), (status.read, status.unread));
+    let backup = config.library_file.with_file_name("library_file.v1.backup");
+    assert_eq!(old, std::fs::read_to_string(backup).unwrap());
+    let library = Library::load(&config).unwrap();
+    assert_eq!(SCHEMA_VERSION, library.schema_version());
+    assert_eq!(
+        Some("rust-lang.org".to_owned()),
+        library.read().articles["1001"].domain
+    );
+    assert_eq!(
+        "Gardening: a primer",
+        library.unread().articles["1002"].title
+    );
+    assert!(!Library::migrate(&config, false).unwrap().is_needed());
+}
+
+#[test]
+fn restores_snapshots_taken_before_destructive_commands() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    let mut config = pocket.authorized_configuration(home.path());
+    config.set("snapshot_retention", "3").unwrap();
+    Library::renew(&config).unwrap();
+    Library::delete(&config, "1").unwrap();
+
+    let snapshots = Snapshot::list(&config).unwrap();
+    let operations: Vec<&str> = snapshots.iter().map(|s| s.operation.as_str()).collect();
+    assert_eq!(vec!["renew", "delete"], operations);
+
+    // Back to before the delete, outbox included
+    Snapshot::restore(&config, &snapshots[1].id).unwrap();
+    assert!(Library::load(&config)
+        .unwrap()
+        .unread()
+        .articles
+        .contains_key("1"));
+    assert!(Outbox::load(&config).unwrap().pending().is_empty());
+
+    // The restore took its own snapshot, and older ones rotate out
+    Library::renew(&config).unwrap();
+    let operations: Vec<String> = Snapshot::list(&config)
+        .unwrap()
+        .into_iter()
+        .map(|snapshot| snapshot.operation)
+        .collect();
+    assert_eq!(vec!["delete", "restore", "renew"], operations);
+
+    assert!(Snapshot::restore(&config, "missing").is_err());
+}
+
+#[test]
+fn encrypts_backups_and_older_snapshots() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    // Snapshots taken before encryption was turned on
+    let mut config = pocket.authorized_configuration(home.path());
+    Library::renew(&config).unwrap();
+    Library::pick(&config, Some(1)).unwrap();
+    Library::renew(&config).unwrap();
+
+    std::fs::write(
+        &config.library_file,
+        include_str!("fixtures/baseline_library_file"),
+    )
+    .unwrap();
+    config.set("encryption", "true").unwrap();
+    config.set_passphrase("correct horse").unwrap();
+    Library::renew(&config).unwrap();
+
+    let backup = config.library_file.with_file_name("library_file.v1.backup");
+    assert!(pickpocket::crypto::is_encrypted(&backup));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(&backup).unwrap();
+        assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+    }
+
+    let snapshots = Snapshot::list(&config).unwrap();
+    assert_eq!(3, snapshots.len());
+    for snapshot in &snapshots {
+        let folder = config.snapshots_folder.join(&snapshot.id);
+        for name in &["library", "outbox"] {
+            let path = folder.join(name);
+            if path.exists() {
+                assert!(
+                    pickpocket::crypto::is_encrypted(&path),
+                    "{}",
+                    path.display()
+                );
+            }
+        }
+    }
+    assert!(config
+        .snapshots_folder
+        .join(&snapshots[1].id)
+        .join("outbox")
+        .exists());
+}
+
+#[test]
+fn encrypts_the_library_and_tokens_with_a_passphrase() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.add_items(3);
+
+    // Written before encryption was turned on
+    let mut config = pocket.authorized_configuration(home.path());
+    assert!(!pickpocket::crypto::is_encrypted(
+        &config.authorization_token_file
+    ));
+    config.set("encryption", "true").unwrap();
+    config.set_passphrase("correct horse").unwrap();
+
+    Library::renew(&config).unwrap();
+    Library::pick(&config, Some(1)).unwrap();
+    let status = Library::status(&config).unwrap();
+    assert_eq!((1, 2), (status.read, status.unread));
+
+    for path in &[
+        &config.library_file,
+        &config.authorization_token_file,
+        &config.history_file,
+        &config.outbox_file,
+    ] {
+        assert!(pickpocket::crypto::is_encrypted(path), "{}", path.display());
+    }
+    let snapshot = &Snapshot::list(&config).unwrap()[0];
+    let snapshot_library = config.snapshots_folder.join(&snapshot.id).join("library");
+    assert!(pickpocket::crypto::is_encrypted(&snapshot_library));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(&config.authorization_token_file).unwrap();
+        assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+    }
+
+    let mut locked = config.clone();
+    locked.passphrase = None;
+    match Library::load(&locked) {
+        Err(PickpocketError::MissingConfiguration(message)) => {
+            assert!(message.contains("PICKPOCKET_PASSPHRASE"))
+        }
+        other => panic!("Expected a missing passphrase, got {:?}", other),
+    }
+
+    locked.set_passphrase("battery staple").unwrap();
+    let error = Library::status(&locked).unwrap_err();
+    assert_eq!(12, error.exit_code());
+}
@@ -0,0 +1,68 @@
+mod support;
+
+use pickpocket::{OAuth, PickpocketError, TokenHandler};
+use support::{Failure, MockPocket};
+
+#[test]
+fn requests_authorization_and_saves_the_oauth_token() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    let config = pocket.configuration(home.path());
+
+    let url = OAuth::request_authorization(&config).unwrap();
+
+    assert!(url.starts_with(&format!("{}/auth/authorize?", pocket.url)));
+    assert!(url.contains(&format!("request_token={}", support::REQUEST_TOKEN)));
+
+    let token_handler = TokenHandler::with_configuration(config);
+    assert_eq!(support::REQUEST_TOKEN, token_handler.read_oauth().unwrap());
+}
+
+#[tokio::test]
+async fn authorizes_the_oauth_token() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    let config = pocket.configuration(home.path());
+
+    OAuth::request_authorization_async(&config).await.unwrap();
+    let access_token = OAuth::authorize_async(&config).await.unwrap();
+
+    assert_eq!(support::ACCESS_TOKEN, access_token);
+
+    let token_handler = TokenHandler::with_configuration(config);
+    assert_eq!(support::ACCESS_TOKEN, token_handler.read_auth().unwrap());
+}
+
+#[test]
+fn does_not_authorize_without_an_oauth_token() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    let config = pocket.configuration(home.path());
+
+    match OAuth::authorize(&config) {
+        Err(PickpocketError::MissingToken(_)) => {}
+        other => panic!("Expected a missing token error, got {:?}", other),
+    }
+}
+
+#[test]
+fn reports_pocket_errors() {
+    let home = tempfile::tempdir().unwrap();
+    let pocket = MockPocket::start();
+    pocket.fail(
+        "/v3/oauth/request",
+        Failure::Status(403, "Invalid consumer key."),
+    );
+    let config = pocket.configuration(home.path());
+
+    match OAuth::request_authorization(&config) {
+        Err(PickpocketError::Pocket { status, message }) => {
+            assert_eq!(403, status);
+            assert_eq!("Invalid consumer key.", message);
+        }
+        other => panic!("Expected a Pocket error, got {:?}", other),
+    }
+
+    let token_handler = TokenHandler::with_configuration(config);
+    assert!(token_handler.read_oauth().is_err());
+}
@@ -0,0 +1,426 @@
+//! In-process fake Pocket server, implementing the v3 endpoints Pickpocket uses.
+#![allow(dead_code)]
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use pickpocket::{Configuration, TokenHandler};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+pub static CONSUMER_KEY: &str = "mock-consumer-key";
+pub static REQUEST_TOKEN: &str = "mock-request-token";
+pub static ACCESS_TOKEN: &str = "mock-access-token";
+
+/// How a request to the mock server should fail.
+#[derive(Clone, Debug)]
+pub enum Failure {
+    /// Answers with the given status and `X-Error` header.
+    Status(u16, &'static str),
+    /// Answers 200 with a body that is not valid JSON.
+    MalformedJson,
+}
+
+/// A request received by the mock server.
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub path: String,
+    pub form: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Pocket's notion of time: bumped on every change, sent back as `since`
+    clock: i64,
+    items: Vec<(String, Value)>,
+    actions: Vec<Value>,
+    rejected: Vec<String>,
+    requests: Vec<Received>,
+    failures: HashMap<String, Failure>,
+    page_failures: HashMap<usize, Failure>,
+    page_delays: HashMap<usize, Duration>,
+    /// Sends the first page whatever the offset asked for
+    ignore_offset: bool,
+    /// Most items sent per page, whatever the count asked for
+    max_count: Option<usize>,
+}
+
+pub struct MockPocket {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockPocket {
+    /// Starts the server on a random local port, on its own thread and runtime,
+    /// so it can serve both blocking and async clients.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+
+        let server_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async move {
+                let make_service = make_service_fn(move |_| {
+                    let state = server_state.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |request| {
+                            handle(state.clone(), request)
+                        }))
+                    }
+                });
+
+                Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(make_service)
+                    .with_graceful_shutdown(async {
+                        shutdown_signal.await.ok();
+                    })
+                    .await
+                    .unwrap();
+            });
+        });
+
+        Self {
+            url,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+
+    /// A configuration pointing every endpoint to this server, keeping files on `home`.
+    pub fn configuration(&self, home: &Path) -> Configuration {
+        let mut config = Configuration::default();
+        config.set("home_folder", home.to_str().unwrap()).unwrap();
+        config.consumer_key = CONSUMER_KEY.to_owned();
+        config.pocket_homepage = self.url.clone();
+        config.pocket_oauth_request_url = format!("{}/v3/oauth/request", self.url);
+        config.pocket_oauth_authorize_url = format!("{}/v3/oauth/authorize", self.url);
+        config.pocket_user_authorize_url = format!("{}/auth/authorize", self.url);
+        config.pocket_retrieve_url = format!("{}/v3/get", self.url);
+        config.pocket_send_url = format!("{}/v3/send", self.url);
+        config
+    }
+
+    /// Same as [`MockPocket::configuration`], with an access token already saved.
+    pub fn authorized_configuration(&self, home: &Path) -> Configuration {
+        let config = self.configuration(home);
+        TokenHandler::with_configuration(config.clone())
+            .save_auth(ACCESS_TOKEN)
+            .unwrap();
+        config
+    }
+
+    /// A Pocket item, as returned by `/v3/get`.
+    pub fn item(id: &str) -> Value {
+        json!({
+            "item_id": id,
+            "resolved_id": id,
+            "given_url": format!("https://example.com/articles/{}", id),
+            "given_title": format!("Given title {}", id),
+            "resolved_title": format!("Article {}", id),
+            "favorite": "0",
+            "status": "0",
+            "word_count": "1000",
+            "time_added": "1600000000",
+            "excerpt": format!("Excerpt of article {}", id),
+            "lang": "en",
+            "is_article": "1",
+            "has_video": "0",
+            "has_image": "1",
+            "tags": { "rust": { "item_id": id, "tag": "rust" } },
+            "authors": { "1": { "author_id": "1", "name": "Jane Doe" } },
+        })
+    }
+
+    pub fn add_item(&self, id: &str, mut item: Value) {
+        let mut state = state_lock(&self.state);
+        item["time_updated"] = json!(state.tick());
+        state.items.push((id.to_owned(), item));
+    }
+
+    /// Changes an item status remotely: "0" unread, "1" archived, "2" deleted.
+    pub fn set_status(&self, id: &str, status: &str) {
+        let mut state = state_lock(&self.state);
+        let time = state.tick();
+        let (_, item) = state
+            .items
+            .iter_mut()
+            .find(|(item_id, _)| item_id == id)
+            .unwrap();
+        item["status"] = json!(status);
+        item["time_updated"] = json!(time);
+    }
+
+    /// Adds items `1..=count`.
+    pub fn add_items(&self, count: usize) {
+        for id in 1..=count {
+            let id = id.to_string();
+            self.add_item(&id, Self::item(&id));
+        }
+    }
+
+    /// Makes `/v3/send` report every action on `item_id` as failed.
+    pub fn reject_actions_on(&self, item_id: &str) {
+        state_lock(&self.state).rejected.push(item_id.to_owned());
+    }
+
+    /// Makes every request to `path` fail.
+    pub fn fail(&self, path: &str, failure: Failure) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .insert(path.to_owned(), failure);
+    }
+
+    /// Makes the `/v3/get` request for `offset` fail.
+    pub fn fail_page(&self, offset: usize, failure: Failure) {
+        self.state
+            .lock()
+            .unwrap()
+            .page_failures
+            .insert(offset, failure);
+    }
+
+    /// Delays the `/v3/get` response for `offset`.
+    pub fn delay_page(&self, offset: usize, delay: Duration) {
+        self.state.lock().unwrap().page_delays.insert(offset, delay);
+    }
+
+    /// Makes `/v3/get` send the first page whatever the offset asked for.
+    pub fn ignore_offset(&self) {
+        self.state.lock().unwrap().ignore_offset = true;
+    }
+
+    /// Makes `/v3/get` send at most `count` items per page, like Pocket does.
+    pub fn cap_count(&self, count: usize) {
+        self.state.lock().unwrap().max_count = Some(count);
+    }
+
+    /// Actions received on `/v3/send`, in order.
+    pub fn actions(&self) -> Vec<Value> {
+        self.state.lock().unwrap().actions.clone()
+    }
+
+    /// Every request received, in order.
+    pub fn requests(&self) -> Vec<Received> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn requests_to(&self, path: &str) -> Vec<Received> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path == path)
+            .collect()
+    }
+}
+
+impl Drop for MockPocket {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl State {
+    fn tick(&mut self) -> i64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+fn state_lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    state.lock().unwrap()
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let path = request.uri().path().to_owned();
+    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+    let form: HashMap<String, String> = url::form_urlencoded::parse(&body).into_owned().collect();
+
+    let (failure, delay) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(Received {
+            path: path.clone(),
+            form: form.clone(),
+        });
+
+        let offset = offset(&form);
+        let failure = match state.failures.get(&path) {
+            Some(failure) => Some(failure.clone()),
+            None if path == "/v3/get" => state.page_failures.get(&offset).cloned(),
+            None => None,
+        };
+        let delay = match path.as_str() {
+            "/v3/get" => state.page_delays.get(&offset).cloned(),
+            _ => None,
+        };
+
+        (failure, delay)
+    };
+
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    if let Some(failure) = failure {
+        return Ok(failure_response(failure));
+    }
+
+    if form.get("consumer_key").map(String::as_str) != Some(CONSUMER_KEY) {
+        return Ok(failure_response(Failure::Status(
+            403,
+            "Invalid consumer key.",
+        )));
+    }
+
+    let response = match path.as_str() {
+        "/v3/oauth/request" => form_response(&[("code", REQUEST_TOKEN)]),
+        "/v3/oauth/authorize" => {
+            if form.get("code").map(String::as_str) != Some(REQUEST_TOKEN) {
+                failure_response(Failure::Status(400, "Invalid request token."))
+            } else {
+                form_response(&[("access_token", ACCESS_TOKEN), ("username", "pickpocket")])
+            }
+        }
+        "/v3/get" | "/v3/send" if !authorized(&form) => {
+            failure_response(Failure::Status(401, "Invalid access token."))
+        }
+        "/v3/get" => retrieve(&state.lock().unwrap(), &form),
+        "/v3/send" => send(&mut state.lock().unwrap(), &form),
+        _ => failure_response(Failure::Status(404, "Not found.")),
+    };
+
+    Ok(response)
+}
+
+fn authorized(form: &HashMap<String, String>) -> bool {
+    form.get("access_token").map(String::as_str) == Some(ACCESS_TOKEN)
+}
+
+fn offset(form: &HashMap<String, String>) -> usize {
+    form.get("offset")
+        .and_then(|offset| offset.parse().ok())
+        .unwrap_or(0)
+}
+
+fn retrieve(state: &State, form: &HashMap<String, String>) -> Response<Body> {
+    let count = form
+        .get("count")
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(usize::MAX);
+
+    let state_filter = form.get("state").map(String::as_str).unwrap_or("unread");
+    let since: i64 = form
+        .get("since")
+        .and_then(|since| since.parse().ok())
+        .unwrap_or(0);
+
+    let page: Map<String, Value> = state
+        .items
+        .iter()
+        .filter(|(_, item)| match state_filter {
+            "unread" => item["status"] == "0",
+            "archive" => item["status"] == "1",
+            _ => true,
+        })
+        .filter(|(_, item)| item["time_updated"].as_i64().unwrap_or(0) > since)
+        .skip(if state.ignore_offset { 0 } else { offset(form) })
+        .take(count.min(state.max_count.unwrap_or(usize::MAX)))
+        .cloned()
+        .collect();
+
+    // Like Pocket, an empty list is sent as an array instead of an object
+    let list = if page.is_empty() {
+        json!([])
+    } else {
+        Value::Object(page)
+    };
+
+    json_response(json!({ "status": 1, "complete": 1, "list": list, "since": state.clock }))
+}
+
+fn send(state: &mut State, form: &HashMap<String, String>) -> Response<Body> {
+    let actions: Vec<Value> = match form.get("actions").map(|a| serde_json::from_str(a)) {
+        Some(Ok(actions)) => actions,
+        _ => return failure_response(Failure::Status(400, "Invalid actions.")),
+    };
+
+    let action_results: Vec<Value> = actions
+        .iter()
+        .map(|action| {
+            let time = state.tick();
+            let item_id = action["item_id"].as_str().unwrap_or_default();
+            if state.rejected.iter().any(|id| id == item_id) {
+                return Value::Bool(false);
+            }
+            let item = state.items.iter_mut().find(|(id, _)| id == item_id);
+
+            let status = match action["action"].as_str() {
+                Some("archive") => "1",
+                Some("delete") => "2",
+                _ => return Value::Bool(true),
+            };
+            if let Some((_, item)) = item {
+                item["status"] = json!(status);
+                item["time_updated"] = json!(time);
+            }
+
+            Value::Bool(true)
+        })
+        .collect();
+    state.actions.extend(actions);
+
+    json_response(json!({ "status": 1, "action_results": action_results }))
+}
+
+fn json_response(body: Value) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn form_response(fields: &[(&str, &str)]) -> Response<Body> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish();
+
+    Response::builder()
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn failure_response(failure: Failure) -> Response<Body> {
+    match failure {
+        Failure::Status(status, message) => Response::builder()
+            .status(StatusCode::from_u16(status).unwrap())
+            .header("X-Error-Code", status.to_string())
+            .header("X-Error", message)
+            .body(Body::empty())
+            .unwrap(),
+        Failure::MalformedJson => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Body::from("{\"status\": 1, \"list\": {"))
+            .unwrap(),
+    }
+}